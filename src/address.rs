@@ -0,0 +1,87 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+const ADDRESS_LEN: usize = 20;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address([u8; ADDRESS_LEN]);
+
+impl Address {
+    pub fn from_public_key(public_key: &[u8]) -> Address {
+        let digest = Sha256::digest(public_key);
+        let mut bytes = [0u8; ADDRESS_LEN];
+        bytes.copy_from_slice(&digest[..ADDRESS_LEN]);
+        Address(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; ADDRESS_LEN] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Address({})", self)
+    }
+}
+
+impl FromStr for Address {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0u8; ADDRESS_LEN];
+        hex::decode_to_slice(s, &mut bytes)?;
+        Ok(Address(bytes))
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::wallet;
+
+    #[test]
+    fn test_address_is_deterministic() {
+        assert_eq!(wallet(1).address(), wallet(1).address());
+        assert_ne!(wallet(1).address(), wallet(2).address());
+        assert_eq!(
+            wallet(1).address(),
+            Address::from_public_key(&wallet(1).public_key())
+        );
+    }
+
+    #[test]
+    fn test_address_hex_round_trip() {
+        let address = wallet(1).address();
+        let text = address.to_string();
+
+        assert_eq!(text.len(), 40);
+        assert_eq!(text.parse::<Address>().unwrap(), address);
+        assert!("not-hex".parse::<Address>().is_err());
+        assert_eq!(
+            serde_json::to_string(&address).unwrap(),
+            format!("\"{}\"", text)
+        );
+    }
+}
@@ -0,0 +1,806 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::DEFAULT_MAX_TRANSACTIONS_PER_BLOCK;
+use crate::difficulty::{self, MAX_TARGET};
+use crate::encoding::{Encoder, Sink};
+use crate::error::{BlockchainError, ValidationError};
+use crate::hash::{BlockHasher, Hash, Sha256Hasher};
+use crate::merkle;
+use crate::transaction::Transaction;
+
+/// The part of a block that its hash commits to. The transactions only come
+/// in through their merkle root, so headers can be checked without them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub network_id: u32,
+    pub id: u64,
+    pub timestamp: u64,
+    pub merkle_root: Hash,
+    pub previous_hash: Hash,
+    pub nonce: u64,
+    pub difficulty: usize,
+    pub target: [u8; 32],
+}
+
+impl BlockHeader {
+    /// Streams the fields into SHA256 without laying them out in a buffer
+    /// first; the result is the same as [`BlockHeader::calculate_hash_with`]
+    /// and [`Sha256Hasher`].
+    pub fn calculate_hash(&self) -> Hash {
+        let mut encoder = Encoder::hashing();
+        self.encode_into(&mut encoder);
+        encoder.finish_hash()
+    }
+
+    pub fn calculate_hash_with(&self, hasher: &dyn BlockHasher) -> Hash {
+        Hash::from_bytes(hasher.hash(&self.encode()))
+    }
+
+    /// The bytes the hash is taken over, laid out by [`Encoder`] in the order
+    /// of the fields above.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        self.encode_into(&mut encoder);
+        encoder.finish()
+    }
+
+    fn encode_into<S: Sink>(&self, encoder: &mut Encoder<S>) {
+        encoder
+            .u64(u64::from(self.network_id))
+            .u64(self.id)
+            .u64(self.timestamp)
+            .raw(self.merkle_root.as_bytes())
+            .raw(self.previous_hash.as_bytes())
+            .u64(self.nonce)
+            .u64(self.difficulty as u64)
+            .raw(&self.target);
+    }
+
+    /// Whether `hash` is below the header's target, and that target is no
+    /// easier than its difficulty.
+    pub fn meets_target(&self, hash: &Hash) -> bool {
+        meets_difficulty(hash, self.difficulty, &self.target)
+    }
+}
+
+/// Checks that `headers` continue the chain from the trusted `anchor`: each
+/// one follows the one before it in id, network and previous hash, and meets
+/// its target. Headers come from [`crate::Blockchain::chain_proof`] and are
+/// hashed with `hasher`, which has to be the chain's own, tag included: see
+/// [`crate::Blockchain::hasher`] and [`crate::TaggedHasher::wrap`].
+pub fn verify_chain_proof(
+    anchor: &BlockHeader,
+    headers: &[BlockHeader],
+    hasher: &dyn BlockHasher,
+) -> bool {
+    let mut previous = (anchor.id, anchor.calculate_hash_with(hasher));
+    for header in headers {
+        let (previous_id, previous_hash) = previous;
+        let hash = header.calculate_hash_with(hasher);
+        if previous_id.checked_add(1) != Some(header.id)
+            || header.network_id != anchor.network_id
+            || header.previous_hash != previous_hash
+            || !header.meets_target(&hash)
+        {
+            return false;
+        }
+        previous = (header.id, hash);
+    }
+    true
+}
+
+/// What a block list shows of a block. Like [`crate::ChainStats`], leaves
+/// out the coinbase, and a pruned block has nothing left to count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSummary {
+    pub id: u64,
+    pub timestamp: u64,
+    pub tx_count: usize,
+    pub total_value: u64,
+    pub hash: Option<Hash>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Block {
+    pub id: u64,
+    pub timestamp: u64,
+    pub transactions: Vec<Transaction>,
+    pub previous_hash: Hash,
+    pub hash: Option<Hash>,
+    pub nonce: u64,
+    pub difficulty: usize,
+    /// The block hash has to be below this, read as a big-endian number. At
+    /// most as high as the target of `difficulty`, and lower when the block
+    /// was mined to a harder one.
+    #[serde(default = "max_target")]
+    pub target: [u8; 32],
+    pub capacity: usize,
+    /// Set once the transactions have been pruned, so the header still hashes
+    /// the same.
+    #[serde(default)]
+    pub pruned_root: Option<Hash>,
+    #[serde(default)]
+    pub network_id: u32,
+    /// Set when an authority signed the block under proof of authority. Not
+    /// part of the hash, which is what gets signed.
+    #[serde(default)]
+    pub seal: Option<AuthoritySeal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AuthoritySeal {
+    pub signer: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl Block {
+    pub fn new(id: u64, previous_hash: Hash) -> Self {
+        Self::with_capacity(id, previous_hash, DEFAULT_MAX_TRANSACTIONS_PER_BLOCK)
+    }
+
+    pub fn new_with_clock(id: u64, previous_hash: Hash, clock: &dyn Clock) -> Self {
+        let mut block = Self::new(id, previous_hash);
+        block.timestamp = clock.now_secs();
+        block
+    }
+
+    pub fn with_capacity(id: u64, previous_hash: Hash, capacity: usize) -> Self {
+        Self {
+            id,
+            timestamp: SystemClock.now_secs(),
+            transactions: Vec::new(),
+            previous_hash,
+            hash: None,
+            nonce: 0,
+            difficulty: 0,
+            target: MAX_TARGET,
+            capacity,
+            pruned_root: None,
+            network_id: 0,
+            seal: None,
+        }
+    }
+
+    /// Returns `false`, leaving the block untouched, once it is at capacity.
+    /// Filling the last slot seals the block.
+    pub fn add_transaction(&mut self, transaction: Transaction) -> bool {
+        if self.transactions.len() >= self.capacity {
+            return false;
+        }
+        self.transactions.push(transaction);
+        if self.transactions.len() == self.capacity {
+            self.seal();
+        }
+        true
+    }
+
+    pub fn seal(&mut self) {
+        self.hash = Some(self.calculate_hash());
+    }
+
+    /// Bytes the block takes in bincode. Mining does not change it, as long
+    /// as the block has been sealed.
+    pub fn serialized_size(&self) -> usize {
+        bincode::serialized_size(self).map_or(usize::MAX, |size| size as usize)
+    }
+
+    pub fn total_fees(&self) -> u64 {
+        self.transactions
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .fold(0, |total: u64, tx| total.saturating_add(tx.fee))
+    }
+
+    pub fn summary(&self) -> BlockSummary {
+        let mut summary = BlockSummary {
+            id: self.id,
+            timestamp: self.timestamp,
+            tx_count: 0,
+            total_value: 0,
+            hash: self.hash,
+        };
+        for tx in self.transactions.iter().filter(|tx| !tx.is_coinbase()) {
+            summary.tx_count += 1;
+            for output in &tx.outputs {
+                summary.total_value = summary.total_value.saturating_add(output.amount);
+            }
+        }
+        summary
+    }
+
+    /// Drops the transactions, keeping their merkle root in their place.
+    pub fn prune(&mut self) {
+        if self.pruned_root.is_none() {
+            self.pruned_root = Some(self.merkle_root());
+            self.transactions = Vec::new();
+        }
+    }
+
+    pub fn is_pruned(&self) -> bool {
+        self.pruned_root.is_some()
+    }
+
+    pub fn merkle_root(&self) -> Hash {
+        if let Some(root) = self.pruned_root {
+            return root;
+        }
+        let leaves: Vec<Hash> = self.transactions.iter().map(Transaction::hash).collect();
+        merkle::merkle_root(&leaves)
+    }
+
+    pub fn merkle_proof(&self, tx_id: u64) -> Option<Vec<(Hash, bool)>> {
+        let index = self.transactions.iter().position(|tx| tx.id == tx_id)?;
+        let leaves: Vec<Hash> = self.transactions.iter().map(Transaction::hash).collect();
+        Some(merkle::merkle_proof(&leaves, index))
+    }
+
+    pub fn calculate_hash(&self) -> Hash {
+        self.header().calculate_hash()
+    }
+
+    pub fn calculate_hash_with(&self, hasher: &dyn BlockHasher) -> Hash {
+        self.header().calculate_hash_with(hasher)
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            network_id: self.network_id,
+            id: self.id,
+            timestamp: self.timestamp,
+            merkle_root: self.merkle_root(),
+            previous_hash: self.previous_hash,
+            nonce: self.nonce,
+            difficulty: self.difficulty,
+            target: self.target,
+        }
+    }
+
+    /// Checks the block on its own: it is sealed, its hash is right as
+    /// `hasher` computes it and it links to `expected_previous_hash`. Pass
+    /// [`Blockchain::hasher`](crate::Blockchain::hasher) for a chain's blocks.
+    pub fn verify(
+        &self,
+        expected_previous_hash: &Hash,
+        hasher: &dyn BlockHasher,
+    ) -> Result<(), ValidationError> {
+        self.verify_hash_with(hasher)?;
+        if self.previous_hash != *expected_previous_hash {
+            return Err(ValidationError::BrokenLink { block_id: self.id });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn verify_hash_with(&self, hasher: &dyn BlockHasher) -> Result<(), ValidationError> {
+        let block_id = self.id;
+        match self.hash {
+            None => Err(ValidationError::MissingHash { block_id }),
+            Some(hash) if hash != self.calculate_hash_with(hasher) => {
+                Err(ValidationError::HashMismatch { block_id })
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub fn mine(&mut self, difficulty: usize) {
+        self.mine_with(difficulty, &Sha256Hasher)
+    }
+
+    pub fn mine_with(&mut self, difficulty: usize, hasher: &dyn BlockHasher) {
+        self.difficulty = difficulty;
+        self.mine_to_target(difficulty::leading_zeros_target(difficulty), hasher);
+    }
+
+    /// Mines below `target`, which may sit anywhere under the target of the
+    /// block's difficulty, e.g. one from [`crate::difficulty_to_target`].
+    pub fn mine_to_target(&mut self, target: [u8; 32], hasher: &dyn BlockHasher) {
+        self.target = target;
+        self.nonce = 0;
+
+        let mut hash = self.calculate_hash_with(hasher);
+        while !difficulty::meets_target(&hash, &target) {
+            self.nonce += 1;
+            hash = self.calculate_hash_with(hasher);
+        }
+        self.hash = Some(hash);
+    }
+
+    pub fn meets_difficulty(&self) -> bool {
+        self.hash
+            .as_ref()
+            .is_some_and(|hash| meets_difficulty(hash, self.difficulty, &self.target))
+    }
+}
+
+/// Collects a block's contents and seals it once, in [`BlockBuilder::build`].
+/// Only the genesis block (id 0) may leave out the previous hash.
+#[derive(Debug, Clone)]
+pub struct BlockBuilder {
+    id: u64,
+    previous_hash: Option<Hash>,
+    timestamp: Option<u64>,
+    capacity: usize,
+    transactions: Vec<Transaction>,
+}
+
+impl BlockBuilder {
+    pub fn new(id: u64) -> Self {
+        Self {
+            id,
+            previous_hash: None,
+            timestamp: None,
+            capacity: DEFAULT_MAX_TRANSACTIONS_PER_BLOCK,
+            transactions: Vec::new(),
+        }
+    }
+
+    pub fn previous_hash(mut self, previous_hash: Hash) -> Self {
+        self.previous_hash = Some(previous_hash);
+        self
+    }
+
+    /// Overrides the current time, e.g. for reproducible hashes.
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn transaction(mut self, transaction: Transaction) -> Self {
+        self.transactions.push(transaction);
+        self
+    }
+
+    pub fn build(self) -> Result<Block, BlockchainError> {
+        let previous_hash = match self.previous_hash {
+            Some(hash) => hash,
+            None if self.id == 0 => Hash::ZERO,
+            None => return Err(BlockchainError::MissingPreviousHash),
+        };
+        if self.transactions.len() > self.capacity {
+            return Err(BlockchainError::WrongTransactionCount {
+                min: 0,
+                max: self.capacity,
+                got: self.transactions.len(),
+            });
+        }
+
+        let mut block = Block::with_capacity(self.id, previous_hash, self.capacity);
+        if let Some(timestamp) = self.timestamp {
+            block.timestamp = timestamp;
+        }
+        block.transactions = self.transactions;
+        block.seal();
+        Ok(block)
+    }
+}
+
+fn meets_difficulty(hash: &Hash, difficulty: usize, target: &[u8; 32]) -> bool {
+    *target <= difficulty::leading_zeros_target(difficulty)
+        && difficulty::meets_target(hash, target)
+}
+
+fn max_target() -> [u8; 32] {
+    MAX_TARGET
+}
+
+/// Formats seconds since the epoch as a UTC date and time.
+fn format_timestamp(timestamp: u64) -> String {
+    let days = timestamp / 86_400;
+    let seconds = timestamp % 86_400;
+
+    // Civil date from a day count, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hash = self
+            .hash
+            .map(|hash| hash.short())
+            .unwrap_or_else(|| "unsealed".to_string());
+        write!(
+            f,
+            "Block #{} | {} | {} txs | hash {} | prev {}",
+            self.id,
+            format_timestamp(self.timestamp),
+            self.transactions.len(),
+            hash,
+            self.previous_hash.short()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::hash::TaggedHasher;
+    use crate::merkle::verify_merkle_proof;
+    use crate::test_support::{address, issue};
+    use crate::utxo::TxOutput;
+
+    #[test]
+    fn test_block_creation() {
+        let block = Block::new(1, Hash::ZERO);
+        assert_eq!(block.id, 1);
+        assert_eq!(block.transactions.len(), 0);
+    }
+
+    #[test]
+    fn test_transaction_addition() {
+        let mut block = Block::new(1, Hash::ZERO);
+        let transaction = Transaction {
+            id: 1,
+            origin: address("Alice"),
+            outputs: vec![TxOutput::new(address("Bob"), 50)],
+            ..Default::default()
+        };
+        block.add_transaction(transaction.clone());
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(block.transactions[0].id, 1);
+    }
+
+    #[test]
+    fn test_block_hashing() {
+        let mut block = Block::new(1, Hash::ZERO);
+        for i in 1..=5 {
+            let transaction = Transaction {
+                id: i,
+                origin: address(&format!("Sender{}", i)),
+                outputs: vec![TxOutput::new(address(&format!("Receiver{}", i)), i * 10)],
+                ..Default::default()
+            };
+            block.add_transaction(transaction);
+        }
+        assert!(block.hash.is_some());
+    }
+
+    #[test]
+    fn test_mining_meets_difficulty() {
+        let mut block = Block::new(1, Hash::ZERO);
+        block.mine(2);
+
+        let hash = block.hash.unwrap();
+        assert!(hash.to_string().starts_with("00"));
+        assert_eq!(hash, block.calculate_hash());
+        assert!(block.meets_difficulty());
+    }
+
+    #[test]
+    fn test_mining_to_a_fractional_target() {
+        let mut block = Block::new(1, Hash::ZERO);
+        block.difficulty = 1;
+        let target = difficulty::difficulty_to_target(100.0);
+        block.mine_to_target(target, &Sha256Hasher);
+
+        assert!(block.hash.unwrap().as_bytes() < &target);
+        assert!(block.meets_difficulty());
+
+        // A target easier than the difficulty does not count
+        block.difficulty = 2;
+        assert!(!block.meets_difficulty());
+    }
+
+    #[test]
+    fn test_mining_with_zero_difficulty_needs_no_work() {
+        let mut block = Block::new(1, Hash::ZERO);
+        block.mine(0);
+
+        assert_eq!(block.nonce, 0);
+        assert_eq!(block.hash, Some(block.calculate_hash()));
+    }
+
+    #[test]
+    fn test_merkle_root_changes_with_any_transaction() {
+        let mut block = Block::new(1, Hash::ZERO);
+        for i in 1..=5 {
+            block.add_transaction(Transaction {
+                id: i,
+                origin: address(&format!("Sender{}", i)),
+                outputs: vec![TxOutput::new(address(&format!("Receiver{}", i)), i * 10)],
+                ..Default::default()
+            });
+        }
+        let root = block.merkle_root();
+
+        for index in 0..block.transactions.len() {
+            let mut tampered = block.clone();
+            tampered.transactions[index].outputs[0].amount += 1;
+            assert_ne!(tampered.merkle_root(), root);
+        }
+
+        let mut reordered = block.clone();
+        reordered.transactions.swap(0, 1);
+        assert_ne!(reordered.merkle_root(), root);
+    }
+
+    #[test]
+    fn test_merkle_proof_for_transaction() {
+        let mut block = Block::new(1, Hash::ZERO);
+        for i in 1..=5 {
+            block.add_transaction(Transaction {
+                id: i,
+                origin: address(&format!("Sender{}", i)),
+                outputs: vec![TxOutput::new(address(&format!("Receiver{}", i)), i * 10)],
+                ..Default::default()
+            });
+        }
+        let root = block.merkle_root();
+
+        let proof = block.merkle_proof(3).unwrap();
+        let leaf = block.transactions[2].hash();
+        assert!(verify_merkle_proof(&leaf, &proof, &root));
+        assert!(!verify_merkle_proof(
+            &block.transactions[0].hash(),
+            &proof,
+            &root
+        ));
+
+        assert!(block.merkle_proof(42).is_none());
+    }
+
+    #[test]
+    fn test_display_summarizes_block() {
+        let mut block = Block::with_capacity(3, Hash::from_bytes([0xab; 32]), 2);
+        block.timestamp = 1_700_000_000;
+        assert_eq!(
+            block.to_string(),
+            "Block #3 | 2023-11-14 22:13:20 UTC | 0 txs | hash unsealed | prev abababab"
+        );
+
+        block.hash = Some(Hash::from_bytes([0x0f; 32]));
+        block.transactions.push(Transaction::default());
+        assert_eq!(
+            block.to_string(),
+            "Block #3 | 2023-11-14 22:13:20 UTC | 1 txs | hash 0f0f0f0f | prev abababab"
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(4_102_444_799), "2099-12-31 23:59:59 UTC");
+    }
+
+    #[test]
+    fn test_default_hasher_is_sha256() {
+        let block = Block::new(4, Hash::from_bytes([7; 32]));
+        assert_eq!(
+            block.calculate_hash(),
+            crate::hash::sha256(block.header().encode())
+        );
+        assert_eq!(
+            block.calculate_hash(),
+            block.calculate_hash_with(&Sha256Hasher)
+        );
+    }
+
+    #[test]
+    fn test_partial_block_can_be_sealed() {
+        let mut block = Block::with_capacity(1, Hash::ZERO, 3);
+        assert!(block.add_transaction(issue([1]).remove(0)));
+        assert!(block.hash.is_none());
+
+        block.seal();
+        assert_eq!(block.hash, Some(block.calculate_hash()));
+
+        assert!(block.add_transaction(issue([2]).remove(0)));
+        assert!(block.add_transaction(issue([3]).remove(0)));
+        assert!(!block.add_transaction(issue([4]).remove(0)));
+        assert_eq!(block.transactions.len(), 3);
+    }
+
+    #[test]
+    fn test_builder_with_fixed_timestamp_hashes_the_same() {
+        let build = || {
+            BlockBuilder::new(1)
+                .previous_hash(Hash::from_bytes([0xab; 32]))
+                .timestamp(1_700_000_000)
+                .transaction(issue([1]).remove(0))
+                .transaction(issue([2]).remove(0))
+                .build()
+                .unwrap()
+        };
+
+        let block = build();
+        assert_eq!(block.timestamp, 1_700_000_000);
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(block.hash, Some(block.calculate_hash()));
+        assert_eq!(block.hash, build().hash);
+    }
+
+    #[test]
+    fn test_builder_rejects_incomplete_or_oversized_blocks() {
+        assert_eq!(
+            BlockBuilder::new(1).build().err(),
+            Some(BlockchainError::MissingPreviousHash)
+        );
+        assert!(BlockBuilder::new(0).build().is_ok());
+
+        let mut builder = BlockBuilder::new(0).capacity(1);
+        for tx in issue(1..=2) {
+            builder = builder.transaction(tx);
+        }
+        assert_eq!(
+            builder.build().err(),
+            Some(BlockchainError::WrongTransactionCount {
+                min: 0,
+                max: 1,
+                got: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_a_single_block() {
+        let previous = Hash::from_bytes([0xab; 32]);
+        let mut builder = BlockBuilder::new(1).previous_hash(previous);
+        for tx in issue(1..=3) {
+            builder = builder.transaction(tx);
+        }
+        let block = builder.build().unwrap();
+        assert_eq!(block.verify(&previous, &Sha256Hasher), Ok(()));
+        assert_eq!(
+            block.verify(&Hash::ZERO, &Sha256Hasher),
+            Err(ValidationError::BrokenLink { block_id: 1 })
+        );
+
+        let mut tampered = block.clone();
+        tampered.transactions[1].outputs[0].amount += 1;
+        assert_eq!(
+            tampered.verify(&previous, &Sha256Hasher),
+            Err(ValidationError::HashMismatch { block_id: 1 })
+        );
+
+        tampered.hash = None;
+        assert_eq!(
+            tampered.verify(&previous, &Sha256Hasher),
+            Err(ValidationError::MissingHash { block_id: 1 })
+        );
+
+        let tagged = TaggedHasher::wrap("alpha", Arc::new(Sha256Hasher));
+        let mut block = block;
+        block.hash = Some(block.calculate_hash_with(tagged.as_ref()));
+        assert_eq!(block.verify(&previous, tagged.as_ref()), Ok(()));
+        assert_eq!(
+            block.verify(&previous, &Sha256Hasher),
+            Err(ValidationError::HashMismatch { block_id: 1 })
+        );
+    }
+
+    #[test]
+    fn test_headers_differ_only_in_the_merkle_root() {
+        let [first, second]: [Transaction; 2] = issue([1, 2]).try_into().unwrap();
+        let build = |transactions: [&Transaction; 2]| {
+            let mut builder = BlockBuilder::new(1)
+                .previous_hash(Hash::ZERO)
+                .timestamp(1_000);
+            for tx in transactions {
+                builder = builder.transaction(tx.clone());
+            }
+            builder.build().unwrap()
+        };
+        let a = build([&first, &second]);
+        let b = build([&second, &first]);
+
+        let (header_a, header_b) = (a.header(), b.header());
+        assert_ne!(header_a, header_b);
+        assert_ne!(a.hash, b.hash);
+        assert_eq!(a.hash, Some(header_a.calculate_hash()));
+        assert_eq!(
+            BlockHeader {
+                merkle_root: header_b.merkle_root,
+                ..header_a
+            },
+            header_b
+        );
+    }
+
+    #[test]
+    fn test_blocks_compare_by_content_and_hash() {
+        let build = || {
+            BlockBuilder::new(1)
+                .previous_hash(Hash::ZERO)
+                .timestamp(1_000)
+                .transaction(Transaction::coinbase(1, address("Miner"), 50))
+                .build()
+                .unwrap()
+        };
+        let block = build();
+        assert_eq!(block, build());
+
+        let mut mined = build();
+        mined.mine(1);
+        assert_ne!(block, mined);
+
+        // Same contents, but not sealed
+        let mut unsealed = build();
+        unsealed.hash = None;
+        assert_ne!(block, unsealed);
+
+        let blocks: std::collections::HashSet<Block> = [block, build(), mined].into();
+        assert_eq!(blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_known_block_hash() {
+        let block = BlockBuilder::new(1)
+            .previous_hash(Hash::from_bytes([7; 32]))
+            .timestamp(1_700_000_000)
+            .transaction(Transaction::coinbase(1, address("Miner"), 50))
+            .build()
+            .unwrap();
+        assert_eq!(block.header().encode().len(), 5 * 8 + 3 * 32);
+        assert_eq!(
+            block.hash.unwrap().to_string(),
+            "6042f30634ea20b748994a0b8fc0660677d2d28f9f2148d5509b6392eba9937a"
+        );
+    }
+
+    #[test]
+    fn test_streamed_hash_matches_the_canonical_encoding() {
+        let mut builder = BlockBuilder::new(2)
+            .previous_hash(Hash::from_bytes([7; 32]))
+            .timestamp(1_700_000_000);
+        for tx in issue(1..=5) {
+            builder = builder.transaction(tx);
+        }
+        let block = builder.build().unwrap();
+
+        let header = block.header();
+        assert_eq!(
+            header.calculate_hash(),
+            header.calculate_hash_with(&Sha256Hasher)
+        );
+        assert_eq!(
+            block.calculate_hash().to_string(),
+            "1bf817d5038e29b1a9b2bd640af707137b9955f033a6c3186728a5623317b39f"
+        );
+    }
+
+    #[test]
+    fn test_summary_counts_transfers_and_their_value() {
+        let mut builder = BlockBuilder::new(1)
+            .previous_hash(Hash::ZERO)
+            .capacity(6)
+            .transaction(Transaction::coinbase(1, address("Miner"), 50));
+        for tx in issue(1..=5) {
+            builder = builder.transaction(tx);
+        }
+        let block = builder.build().unwrap();
+
+        let summary = block.summary();
+        assert_eq!(summary.id, 1);
+        assert_eq!(summary.timestamp, block.timestamp);
+        assert_eq!(summary.tx_count, 5);
+        assert_eq!(summary.total_value, 10 + 20 + 30 + 40 + 50);
+        assert_eq!(summary.hash, block.hash);
+    }
+}
@@ -0,0 +1,4708 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::address::Address;
+use crate::block::{Block, BlockHeader, BlockSummary};
+use crate::clock::{Clock, SystemClock};
+use crate::config::BlockchainConfig;
+use crate::consensus::{Consensus, ConsensusKind};
+use crate::difficulty;
+use crate::error::{BlockchainError, ValidationError};
+use crate::hash::{BlockHasher, Hash, Sha256Hasher, TaggedHasher};
+use crate::mempool::Mempool;
+use crate::transaction::Transaction;
+use crate::utxo::{UtxoOverlay, UtxoSet};
+
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "ChainData")]
+pub struct Blockchain {
+    blocks: Vec<Block>,
+    config: BlockchainConfig,
+    mempool: Mempool,
+    miner: Option<Address>,
+    /// Blocks below this height had their transactions pruned here. They are
+    /// the only blocks whose pruned root is taken on trust.
+    pruned_below: u64,
+    /// State right after the last pruned block, which reindexing starts from
+    /// since those blocks no longer hold their transactions.
+    pruned_state: Option<PrunedState>,
+    #[serde(skip)]
+    hash_index: HashMap<Hash, u64>,
+    #[serde(skip)]
+    tx_index: HashMap<u64, u64>,
+    /// Block ids by transaction hash, coinbases included.
+    #[serde(skip)]
+    tx_hash_index: HashMap<Hash, u64>,
+    /// Highest transfer id on the chain, kept up to date so numbering a new
+    /// transaction does not have to look through all of them.
+    #[serde(skip)]
+    max_tx_id: u64,
+    #[serde(skip)]
+    utxos: UtxoSet,
+    #[serde(skip)]
+    nonces: HashMap<Address, u64>,
+    #[serde(skip)]
+    checkpoint: Option<(u64, Hash)>,
+    #[serde(skip)]
+    hasher: Arc<dyn BlockHasher>,
+    #[serde(serialize_with = "serialize_consensus")]
+    consensus: Arc<dyn Consensus>,
+    #[serde(skip)]
+    clock: Box<dyn Clock>,
+    #[serde(skip)]
+    listeners: Vec<BlockListener>,
+    /// Blocks whose parent has not arrived yet, oldest first.
+    #[serde(skip)]
+    orphans: VecDeque<Block>,
+    /// Known blocks off the chain, by hash. Together with the chain they form
+    /// a tree through their previous hashes.
+    #[serde(skip)]
+    branches: HashMap<Hash, Block>,
+}
+
+type BlockListener = Box<dyn Fn(&Block) + Send + Sync>;
+
+/// Where [`Blockchain::accept_block`] put a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockStatus {
+    /// On the chain, on top of the tip or with a branch the chain switched to.
+    OnChain,
+    /// On a branch with no more work than the chain.
+    OnBranch,
+    /// Held back until its parent arrives.
+    Orphan,
+}
+
+/// Serialized form of a chain. Lookup indexes are derived data and get rebuilt
+/// when a chain is read back.
+#[derive(Deserialize)]
+struct ChainData {
+    blocks: Vec<Block>,
+    config: BlockchainConfig,
+    #[serde(default)]
+    mempool: Mempool,
+    #[serde(default)]
+    miner: Option<Address>,
+    #[serde(default)]
+    pruned_below: u64,
+    #[serde(default)]
+    pruned_state: Option<PrunedState>,
+    /// Missing from files saved before the rule was; those go by the config.
+    #[serde(default)]
+    consensus: Option<ConsensusKind>,
+}
+
+fn serialize_consensus<S: serde::Serializer>(
+    consensus: &Arc<dyn Consensus>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    Some(consensus.kind()).serialize(serializer)
+}
+
+impl TryFrom<ChainData> for Blockchain {
+    type Error = BlockchainError;
+
+    fn try_from(data: ChainData) -> Result<Self, Self::Error> {
+        let mut blockchain = Blockchain::unindexed(data.blocks, data.config);
+        blockchain.mempool = data.mempool;
+        blockchain.miner = data.miner;
+        blockchain.pruned_below = data.pruned_below;
+        blockchain.pruned_state = data.pruned_state;
+        if let Some(kind) = data.consensus {
+            blockchain.consensus = Arc::from(kind.build());
+        }
+        blockchain.reindex()?;
+        Ok(blockchain)
+    }
+}
+
+/// Balances, nonces and the highest transfer id as they stood right after a
+/// block, kept for when the blocks up to it are pruned.
+#[derive(Clone, Serialize, Deserialize)]
+struct PrunedState {
+    utxos: UtxoSet,
+    nonces: HashMap<Address, u64>,
+    max_tx_id: u64,
+}
+
+impl Blockchain {
+    /// Starts from a genesis block paying out `config.initial_allocations`.
+    /// Panics if they add up to more than a `u64` holds for one account.
+    pub fn new(config: BlockchainConfig) -> Self {
+        let genesis = genesis_block(&config, &SystemClock);
+        Self::with_genesis(config, genesis).expect("the initial allocations overflow")
+    }
+
+    /// Starts from a custom first block, e.g. one carrying initial allocations.
+    /// The genesis block must have id 0 and the zero previous hash, and is
+    /// sealed here if it has no hash. Fails if its transactions cannot be
+    /// played, e.g. when they spend outputs that do not exist.
+    pub fn with_genesis(config: BlockchainConfig, genesis: Block) -> Result<Self, BlockchainError> {
+        Self::from_genesis(config, genesis, Arc::new(Sha256Hasher))
+    }
+
+    /// Seals and validates blocks with `hasher` instead of SHA256. Chains read
+    /// back from disk always use SHA256. A `chain_tag` in the config is put in
+    /// front of what either of them hashes.
+    pub fn with_hasher(config: BlockchainConfig, hasher: impl BlockHasher + 'static) -> Self {
+        let genesis = genesis_block(&config, &SystemClock);
+        Self::from_genesis(config, genesis, Arc::new(hasher))
+            .expect("the initial allocations overflow")
+    }
+
+    /// Takes every timestamp, genesis included, from `clock`. Chains read back
+    /// from disk use the system clock.
+    pub fn with_clock(config: BlockchainConfig, clock: impl Clock + 'static) -> Self {
+        let genesis = genesis_block(&config, &clock);
+        let mut blockchain =
+            Self::with_genesis(config, genesis).expect("the initial allocations overflow");
+        blockchain.clock = Box::new(clock);
+        blockchain
+    }
+
+    /// Seals and checks blocks with `consensus` instead of mining them. Chains
+    /// read back from disk use the rule they were saved with, less its
+    /// signing key.
+    pub fn with_consensus(config: BlockchainConfig, consensus: impl Consensus + 'static) -> Self {
+        let mut blockchain = Self::new(config);
+        blockchain.consensus = Arc::new(consensus);
+        blockchain
+    }
+
+    fn from_genesis(
+        config: BlockchainConfig,
+        mut genesis: Block,
+        hasher: Arc<dyn BlockHasher>,
+    ) -> Result<Self, BlockchainError> {
+        let hasher = TaggedHasher::wrap(&config.chain_tag, hasher);
+        if genesis.hash.is_none() {
+            genesis.hash = Some(genesis.calculate_hash_with(hasher.as_ref()));
+        }
+
+        let mut blockchain = Self::from_parts(vec![genesis], config)?;
+        blockchain.hasher = hasher;
+        Ok(blockchain)
+    }
+
+    fn from_parts(blocks: Vec<Block>, config: BlockchainConfig) -> Result<Self, BlockchainError> {
+        let mut blockchain = Self::unindexed(blocks, config);
+        blockchain.reindex()?;
+        Ok(blockchain)
+    }
+
+    /// The chain with none of its lookups built yet.
+    fn unindexed(blocks: Vec<Block>, config: BlockchainConfig) -> Self {
+        let hasher = TaggedHasher::wrap(&config.chain_tag, Arc::new(Sha256Hasher));
+        let consensus = if config.authorities.is_empty() {
+            ConsensusKind::ProofOfWork
+        } else {
+            ConsensusKind::ProofOfAuthority {
+                authorities: config.authorities.clone(),
+                algorithm: config.signature_algorithm,
+            }
+        };
+        Self {
+            blocks,
+            config,
+            mempool: Mempool::new(),
+            miner: None,
+            pruned_below: 0,
+            pruned_state: None,
+            hash_index: HashMap::new(),
+            tx_index: HashMap::new(),
+            tx_hash_index: HashMap::new(),
+            max_tx_id: 0,
+            utxos: UtxoSet::new(),
+            nonces: HashMap::new(),
+            checkpoint: None,
+            hasher,
+            consensus: Arc::from(consensus.build()),
+            clock: Box::new(SystemClock),
+            listeners: Vec::new(),
+            orphans: VecDeque::new(),
+            branches: HashMap::new(),
+        }
+    }
+
+    /// Rebuilds every lookup from the blocks, starting from what was left of
+    /// the pruned ones. Fails on the first transaction that cannot be played,
+    /// leaving the lookups half built.
+    fn reindex(&mut self) -> Result<(), BlockchainError> {
+        self.hash_index.clear();
+        self.tx_index.clear();
+        self.tx_hash_index.clear();
+        match self.pruned_state.clone() {
+            Some(state) => {
+                self.utxos = state.utxos;
+                self.nonces = state.nonces;
+                self.max_tx_id = state.max_tx_id;
+            }
+            None => {
+                self.utxos = UtxoSet::with_maturity(self.config.coinbase_maturity);
+                self.nonces.clear();
+                self.max_tx_id = 0;
+            }
+        }
+
+        let blocks = std::mem::take(&mut self.blocks);
+        let result = blocks.iter().try_for_each(|block| self.index_block(block));
+        self.blocks = blocks;
+        result
+    }
+
+    fn index_block(&mut self, block: &Block) -> Result<(), BlockchainError> {
+        if let Some(hash) = block.hash {
+            self.hash_index.insert(hash, block.id);
+        }
+        self.utxos.set_height(block.id);
+        for tx in &block.transactions {
+            self.tx_hash_index.insert(tx.hash(), block.id);
+            if !tx.is_coinbase() {
+                self.tx_index.insert(tx.id, block.id);
+                self.max_tx_id = self.max_tx_id.max(tx.id);
+                self.nonces.insert(tx.origin, tx.nonce + 1);
+            }
+            let mints = self.is_issuer(&tx.origin);
+            self.utxos.apply(tx, mints)?;
+        }
+        Ok(())
+    }
+
+    pub fn add_block(
+        &mut self,
+        transactions: Vec<Transaction>,
+        miner: Address,
+    ) -> Result<u64, BlockchainError> {
+        if !self.allows_transaction_count(transactions.len()) {
+            let (min, max) = self.transaction_bounds();
+            return Err(BlockchainError::WrongTransactionCount {
+                min,
+                max,
+                got: transactions.len(),
+            });
+        }
+
+        self.append_block(transactions, miner)
+    }
+
+    /// Drops the transactions of every block but the last `keep_last`. Their
+    /// headers stay, so links and hashes can still be validated. Balances and
+    /// nonces are kept as they stood after the last pruned block, so they
+    /// survive a reload, but the pruned transactions can no longer be looked
+    /// up. Pruned blocks are there for good: nothing pops or replaces them.
+    ///
+    /// Only blocks pruned here are taken without their transactions; pruned
+    /// blocks from peers are refused.
+    pub fn prune(&mut self, keep_last: usize) {
+        let pruned = self.blocks.len().saturating_sub(keep_last);
+        if pruned as u64 <= self.pruned_below {
+            return;
+        }
+        let kept = self.blocks.split_off(pruned);
+        self.reindex().expect("these blocks were indexed before");
+        self.pruned_state = Some(PrunedState {
+            utxos: self.utxos.clone(),
+            nonces: self.nonces.clone(),
+            max_tx_id: self.max_tx_id,
+        });
+        for block in &mut self.blocks {
+            block.prune();
+        }
+        self.pruned_below = pruned as u64;
+        self.blocks.extend(kept);
+        self.reindex().expect("these blocks were indexed before");
+    }
+
+    /// Removes the tip and hands it back, along with any branch or held back
+    /// block built on it. The genesis block and pruned blocks are never
+    /// removed.
+    pub fn pop_block(&mut self) -> Option<Block> {
+        if self.blocks.len() <= 1 || self.height() < self.pruned_below {
+            return None;
+        }
+        let block = self.blocks.pop()?;
+        // Spent outputs are not kept around, so the set is rebuilt from scratch
+        self.reindex().expect("these blocks were indexed before");
+        if let Some(hash) = block.hash {
+            self.forget_descendants(hash);
+        }
+        Some(block)
+    }
+
+    /// Drops the branch and held back blocks that build on `hash`, directly
+    /// or through each other.
+    fn forget_descendants(&mut self, hash: Hash) {
+        let mut parents = vec![hash];
+        while let Some(parent) = parents.pop() {
+            self.orphans.retain(|orphan| orphan.previous_hash != parent);
+            let children: Vec<Hash> = self
+                .branches
+                .iter()
+                .filter(|(_, block)| block.previous_hash == parent)
+                .map(|(&hash, _)| hash)
+                .collect();
+            for child in children {
+                self.branches.remove(&child);
+                parents.push(child);
+            }
+        }
+    }
+
+    /// Replaces the blocks from `height` up with new ones holding
+    /// `new_transactions`, one batch per block, mined on top of what is left.
+    /// Each new block pays the miner of the block it replaces, or of the old
+    /// tip once past it. The replaced blocks are kept as a branch, and their
+    /// transactions that are not in a batch are dropped.
+    ///
+    /// Either every batch makes it onto the chain or the chain is left as it
+    /// was; listeners only hear about the new blocks once all of them are in.
+    pub fn rebuild_from(
+        &mut self,
+        height: u64,
+        new_transactions: Vec<Vec<Transaction>>,
+    ) -> Result<(), BlockchainError> {
+        let tip = self.height();
+        if height == 0 || height > tip {
+            return Err(BlockchainError::HeightOutOfRange { height, tip });
+        }
+        if height < self.pruned_below {
+            return Err(BlockchainError::PrunedHistory {
+                height: self.pruned_below,
+            });
+        }
+        let start = height as usize;
+        let replaced = self.blocks.split_off(start);
+        self.reindex().expect("these blocks were indexed before");
+
+        let listeners = std::mem::take(&mut self.listeners);
+        let mut result = Ok(());
+        for (index, transactions) in new_transactions.into_iter().enumerate() {
+            let paid_by = replaced.get(index).or(replaced.last());
+            let miner = paid_by
+                .and_then(|block| block.transactions.first())
+                .filter(|tx| tx.is_coinbase())
+                .and_then(|coinbase| coinbase.outputs.first())
+                .map_or_else(Address::default, |output| output.recipient);
+            result = self.add_block(transactions, miner).map(|_| ());
+            if result.is_err() {
+                break;
+            }
+        }
+        self.listeners = listeners;
+
+        match result {
+            Ok(()) => {
+                for block in replaced {
+                    if let Some(hash) = block.hash {
+                        self.branches.insert(hash, block);
+                    }
+                }
+                for block in &self.blocks[start..] {
+                    self.notify(block);
+                }
+                Ok(())
+            }
+            Err(err) => {
+                self.blocks.truncate(start);
+                self.blocks.extend(replaced);
+                self.reindex().expect("these blocks were indexed before");
+                Err(err)
+            }
+        }
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+    }
+
+    pub fn set_miner(&mut self, miner: Address) {
+        self.miner = Some(miner);
+    }
+
+    /// Swaps the rule new blocks are sealed and checked with, for instance to
+    /// seal with another authority's key when its turn comes.
+    pub fn set_consensus(&mut self, consensus: impl Consensus + 'static) {
+        self.consensus = Arc::new(consensus);
+    }
+
+    /// Queues a transaction, giving it the next free id if it has none (id 0).
+    pub fn submit_transaction(
+        &mut self,
+        mut transaction: Transaction,
+    ) -> Result<Option<u64>, BlockchainError> {
+        if transaction.id == 0 {
+            transaction.id = self.next_transaction_id();
+        }
+        self.would_accept(&transaction)?;
+        self.mempool.push(transaction);
+
+        match self.miner {
+            Some(miner) if self.mempool.len() >= self.config.max_transactions_per_block => {
+                self.assemble_block(miner).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Runs the checks [`Blockchain::submit_transaction`] would, against the
+    /// chain and whatever is already pending, without queueing anything.
+    pub fn would_accept(&self, transaction: &Transaction) -> Result<(), BlockchainError> {
+        let mut queued = self.mempool.transactions().to_vec();
+        queued.push(transaction.clone());
+        if transaction.id == 0 {
+            queued.last_mut().unwrap().id = self.next_transaction_id();
+        }
+        self.check_transactions(&queued)
+    }
+
+    /// Builds a block from whatever is pending, as long as that meets the
+    /// configured minimum. With nothing pending, that is an empty block if the
+    /// config allows them.
+    pub fn flush_pending(&mut self, miner: Address) -> Result<Option<u64>, BlockchainError> {
+        if !self.can_mine_pending() {
+            return Ok(None);
+        }
+        self.assemble_block(miner).map(Some)
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.mempool.len()
+    }
+
+    pub fn pending_transactions(&self) -> &[Transaction] {
+        self.mempool.transactions()
+    }
+
+    /// One past the highest transfer id on the chain or in the mempool.
+    pub fn next_transaction_id(&self) -> u64 {
+        self.mempool
+            .transactions()
+            .iter()
+            .map(|tx| tx.id)
+            .fold(self.max_tx_id, u64::max)
+            + 1
+    }
+
+    /// [`Blockchain::flush_pending`], with the proof of work done on tokio's
+    /// blocking pool so it does not hold up the runtime.
+    #[cfg(feature = "async")]
+    pub async fn mine_block_async(
+        &mut self,
+        miner: Address,
+    ) -> Result<Option<Block>, BlockchainError> {
+        if !self.can_mine_pending() {
+            return Ok(None);
+        }
+        let mut block = self.assemble_unmined(miner)?;
+
+        let hasher = Arc::clone(&self.hasher);
+        let consensus = Arc::clone(&self.consensus);
+        let block = tokio::task::spawn_blocking(move || {
+            consensus.seal(&mut block, hasher.as_ref());
+            block
+        })
+        .await
+        .expect("the mining task panicked");
+        let pushed = self
+            .check_sealed(&block)
+            .map_err(BlockchainError::InvalidBlock)
+            .and_then(|()| self.push_block(block.clone()));
+        if let Err(err) = pushed {
+            self.mempool.restore(block.transactions[1..].to_vec());
+            return Err(err);
+        }
+        Ok(Some(block))
+    }
+
+    /// Submits transactions from the channel until there are enough for a
+    /// block, then mines it with [`Blockchain::mine_block_async`]. `None`
+    /// means the channel closed first.
+    #[cfg(feature = "async")]
+    pub async fn mine_from_channel(
+        &mut self,
+        transactions: &mut tokio::sync::mpsc::Receiver<Transaction>,
+        miner: Address,
+    ) -> Result<Option<Block>, BlockchainError> {
+        let (min, max) = self.transaction_bounds();
+        while self.mempool.len() < min.max(1) {
+            match transactions.recv().await {
+                Some(tx) => self.submit_transaction(tx)?,
+                None => return Ok(None),
+            };
+        }
+        while self.mempool.len() < max {
+            match transactions.try_recv() {
+                Ok(tx) => self.submit_transaction(tx)?,
+                Err(_) => break,
+            };
+        }
+        self.mine_block_async(miner).await
+    }
+
+    fn assemble_block(&mut self, miner: Address) -> Result<u64, BlockchainError> {
+        let block = self.assemble_unmined(miner)?;
+        let transactions = block.transactions[1..].to_vec();
+        self.mine_and_push(block)
+            .inspect_err(|_| self.mempool.restore(transactions))
+    }
+
+    /// Takes the best pending transactions into a block, putting them back if
+    /// they do not make a valid one. Transactions that would take the block
+    /// past its byte limit stay pending, worst first.
+    fn assemble_unmined(&mut self, miner: Address) -> Result<Block, BlockchainError> {
+        self.mempool.purge_expired(self.clock.now_secs());
+        let mut transactions = self.mempool.take(self.config.max_transactions_per_block);
+        loop {
+            match self.unmined_block(transactions.clone(), miner) {
+                Ok(block) => return Ok(block),
+                Err(BlockchainError::BlockTooLarge { .. }) if transactions.len() > 1 => {
+                    let worst = transactions.pop().expect("more than one transaction");
+                    self.mempool.restore(vec![worst]);
+                }
+                Err(err) => {
+                    self.mempool.restore(transactions);
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    fn append_block(
+        &mut self,
+        transactions: Vec<Transaction>,
+        miner: Address,
+    ) -> Result<u64, BlockchainError> {
+        let block = self.unmined_block(transactions, miner)?;
+        self.mine_and_push(block)
+    }
+
+    fn mine_and_push(&mut self, mut block: Block) -> Result<u64, BlockchainError> {
+        self.consensus.seal(&mut block, self.hasher.as_ref());
+        self.check_sealed(&block)
+            .map_err(BlockchainError::InvalidBlock)?;
+        let id = block.id;
+        self.push_block(block)?;
+        Ok(id)
+    }
+
+    /// Whether a block sealed here passes the checks it would face anywhere
+    /// else, e.g. a proof of authority with no key to sign it, or one on a
+    /// chain that asks for work.
+    fn check_sealed(&self, block: &Block) -> Result<(), ValidationError> {
+        if !block.meets_difficulty() {
+            return Err(ValidationError::InsufficientWork { block_id: block.id });
+        }
+        if !self.consensus.verify(block) {
+            return Err(ValidationError::InvalidSeal { block_id: block.id });
+        }
+        check_proposer(block, &self.config)
+    }
+
+    fn unmined_block(
+        &self,
+        transactions: Vec<Transaction>,
+        miner: Address,
+    ) -> Result<Block, BlockchainError> {
+        self.check_transactions(&transactions)?;
+
+        let latest = self.latest_block().ok_or(BlockchainError::EmptyChain)?;
+        let previous_hash = latest.hash.ok_or(BlockchainError::MissingPreviousHash)?;
+        // One extra slot for the coinbase
+        let capacity = self.config.max_transactions_per_block + 1;
+        let id = latest
+            .id
+            .checked_add(1)
+            .ok_or(BlockchainError::ChainTooLong)?;
+        let mut block = Block::with_capacity(id, previous_hash, capacity);
+        block.timestamp = self.clock.now_secs();
+        block.network_id = self.config.network_id;
+        block.target = self.current_target();
+        block.difficulty = difficulty::target_difficulty(&block.target);
+        check_time_window(&transactions, block.timestamp)?;
+
+        let reward = self.reward_for(block.id, &transactions)?;
+        block.add_transaction(Transaction::coinbase(block.id, miner, reward));
+        for transaction in transactions {
+            block.add_transaction(transaction);
+        }
+        block.seal();
+        self.check_size(&block)?;
+        Ok(block)
+    }
+
+    fn check_size(&self, block: &Block) -> Result<(), BlockchainError> {
+        let size = block.serialized_size();
+        if size > self.config.max_block_bytes {
+            return Err(BlockchainError::BlockTooLarge {
+                block_id: block.id,
+                size,
+                max: self.config.max_block_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Appends a block built elsewhere, e.g. by a peer. It has to pass the
+    /// checks `validate_chain` applies to each block, pay the right coinbase
+    /// and hold transactions that are valid on top of the current tip.
+    ///
+    /// A block whose parent is not known yet is held back instead, and
+    /// placed as soon as the parent is on the chain or a branch. One that
+    /// builds on a block below the tip, or on another branch, starts or
+    /// extends a branch; the chain switches over once that branch holds more
+    /// work and every block on it checks out.
+    pub fn accept_block(&mut self, block: Block) -> Result<BlockStatus, BlockchainError> {
+        // Checked up front so foreign blocks never end up among the orphans
+        check_network(&block, &self.config).map_err(BlockchainError::InvalidBlock)?;
+        check_unpruned(&block).map_err(BlockchainError::InvalidBlock)?;
+        if !self.is_known(&block.previous_hash) {
+            self.hold_orphan(block);
+            return Ok(BlockStatus::Orphan);
+        }
+
+        let hash = block.hash;
+        let mut status = self.place_block(block)?;
+        self.connect_orphans();
+        // Blocks that were waiting on it may have taken its branch past the chain
+        if status == BlockStatus::OnBranch
+            && hash.is_some_and(|hash| self.hash_index.contains_key(&hash))
+        {
+            status = BlockStatus::OnChain;
+        }
+        Ok(status)
+    }
+
+    /// Whether the block is on the chain or on a branch.
+    fn is_known(&self, hash: &Hash) -> bool {
+        self.hash_index.contains_key(hash) || self.branches.contains_key(hash)
+    }
+
+    /// Puts a block whose parent is known on top of the tip, or on a branch.
+    fn place_block(&mut self, block: Block) -> Result<BlockStatus, BlockchainError> {
+        let on_chain = block
+            .hash
+            .is_some_and(|hash| self.hash_index.contains_key(&hash));
+        if Some(block.previous_hash) != self.best_tip() && !on_chain {
+            match self.add_to_branch(block)? {
+                true => Ok(BlockStatus::OnChain),
+                false => Ok(BlockStatus::OnBranch),
+            }
+        } else {
+            self.connect_block(block)?;
+            Ok(BlockStatus::OnChain)
+        }
+    }
+
+    /// Holds a block back until its parent arrives, making room by dropping
+    /// the ones held longest once there are `max_orphans`.
+    fn hold_orphan(&mut self, block: Block) {
+        if self.orphans.contains(&block) {
+            return;
+        }
+        self.orphans.push_back(block);
+        while self.orphans.len() > self.config.max_orphans {
+            self.orphans.pop_front();
+        }
+    }
+
+    /// Whether the chain switched over to the branch the block extends.
+    fn add_to_branch(&mut self, block: Block) -> Result<bool, BlockchainError> {
+        let block_id = block.id;
+        let broken_link = BlockchainError::InvalidBlock(ValidationError::BrokenLink { block_id });
+        let parent_id = match self.hash_index.get(&block.previous_hash) {
+            Some(&id) => id,
+            None => match self.branches.get(&block.previous_hash) {
+                Some(parent) => parent.id,
+                None => return Err(broken_link),
+            },
+        };
+        if parent_id.checked_add(1) != Some(block_id) {
+            return Err(broken_link);
+        }
+        block
+            .verify_hash_with(self.hasher.as_ref())
+            .map_err(BlockchainError::InvalidBlock)?;
+        if !block.meets_difficulty() {
+            return Err(BlockchainError::InvalidBlock(
+                ValidationError::InsufficientWork { block_id },
+            ));
+        }
+        if !self.consensus.verify(&block) {
+            return Err(BlockchainError::InvalidBlock(
+                ValidationError::InvalidSeal { block_id },
+            ));
+        }
+        check_proposer(&block, &self.config).map_err(BlockchainError::InvalidBlock)?;
+        let hash = block.hash.expect("the hash was verified");
+        self.branches.insert(hash, block);
+
+        // Walk down the branch to the block it forks from
+        let mut branch = Vec::new();
+        let mut cursor = hash;
+        while let Some(block) = self.branches.get(&cursor) {
+            cursor = block.previous_hash;
+            branch.push(block.clone());
+        }
+        branch.reverse();
+        let Some(&fork) = self.hash_index.get(&cursor) else {
+            self.branches.remove(&hash);
+            return Err(broken_link);
+        };
+        let fork = fork as usize;
+        let mut candidate = self.blocks[..=fork].to_vec();
+        candidate.extend(branch);
+
+        self.try_replace_chain(candidate).inspect_err(|_| {
+            self.branches.remove(&hash);
+        })
+    }
+
+    /// Hash of the tip of the chain, the branch with the most work.
+    pub fn best_tip(&self) -> Option<Hash> {
+        self.latest_block().and_then(|tip| tip.hash)
+    }
+
+    /// Every block nothing has been built on yet: the best tip first, then
+    /// the ends of the competing branches.
+    pub fn tips(&self) -> Vec<Hash> {
+        let parents: HashSet<Hash> = self
+            .branches
+            .values()
+            .map(|block| block.previous_hash)
+            .collect();
+        let mut others: Vec<Hash> = self
+            .branches
+            .keys()
+            .filter(|hash| !parents.contains(*hash))
+            .copied()
+            .collect();
+        others.sort();
+        self.best_tip().into_iter().chain(others).collect()
+    }
+
+    /// A block known from a branch the chain does not follow.
+    pub fn branch_block(&self, hash: &Hash) -> Option<&Block> {
+        self.branches.get(hash)
+    }
+
+    pub fn orphan_count(&self) -> usize {
+        self.orphans.len()
+    }
+
+    /// Places held back blocks, on the chain or on a branch, for as long as
+    /// one of them has a known parent. The ones that do not fit are dropped.
+    fn connect_orphans(&mut self) {
+        while let Some(index) = self
+            .orphans
+            .iter()
+            .position(|orphan| self.is_known(&orphan.previous_hash))
+        {
+            let orphan = self.orphans.remove(index).expect("the orphan was found");
+            let _ = self.place_block(orphan);
+        }
+    }
+
+    /// Appends blocks built elsewhere, all of them or none. Each one has to
+    /// pass the checks of [`Blockchain::accept_block`] on top of the ones
+    /// before it; listeners only hear about them once the whole batch is in.
+    pub fn add_blocks(&mut self, blocks: Vec<Block>) -> Result<(), BlockchainError> {
+        let start = self.blocks.len();
+        if let Err(err) = self.append_checked(blocks) {
+            self.blocks.truncate(start);
+            self.reindex().expect("these blocks were indexed before");
+            return Err(err);
+        }
+
+        for block in &self.blocks[start..] {
+            self.notify(block);
+        }
+        self.connect_orphans();
+        Ok(())
+    }
+
+    /// Checks each block on top of the ones before it and appends it without
+    /// telling listeners. Stops at the first one that does not fit, leaving
+    /// the lookups for the caller to rebuild.
+    fn append_checked(
+        &mut self,
+        blocks: impl IntoIterator<Item = Block>,
+    ) -> Result<(), BlockchainError> {
+        for block in blocks {
+            self.check_block(&block)?;
+            self.index_block(&block)?;
+            self.blocks.push(block);
+        }
+        Ok(())
+    }
+
+    fn connect_block(&mut self, block: Block) -> Result<(), BlockchainError> {
+        self.check_block(&block)?;
+        self.push_block(block)
+    }
+
+    /// Whether `block` could go on top of the current tip: the next id, a
+    /// link to the tip, a valid hash and enough work, a size within the limit,
+    /// the right coinbase and transactions that are valid there. Nothing
+    /// changes either way; [`Blockchain::accept_block`] runs the same checks
+    /// before appending.
+    pub fn check_block(&self, block: &Block) -> Result<(), BlockchainError> {
+        BlockValidator::after(self)
+            .check(block)
+            .map_err(BlockchainError::InvalidBlock)?;
+        self.check_size(block)?;
+
+        let (coinbase, transactions) = match block.transactions.split_first() {
+            Some((coinbase, rest)) if coinbase.is_coinbase() => (coinbase, rest),
+            _ => return Err(BlockchainError::MissingCoinbase { block_id: block.id }),
+        };
+        if coinbase.id != block.id || coinbase.nonce != 0 {
+            return Err(BlockchainError::InvalidTransaction {
+                id: coinbase.id,
+                reason: "the coinbase should carry the block's id".to_string(),
+            });
+        }
+        self.check_transactions(transactions)?;
+        check_time_window(transactions, block.timestamp)?;
+        let reward = self.reward_for(block.id, transactions)?;
+        if coinbase.quantity()? != reward {
+            return Err(BlockchainError::InvalidTransaction {
+                id: coinbase.id,
+                reason: format!("the coinbase should pay {}", reward),
+            });
+        }
+        Ok(())
+    }
+
+    fn push_block(&mut self, block: Block) -> Result<(), BlockchainError> {
+        if let Err(err) = self.index_block(&block) {
+            self.reindex().expect("these blocks were indexed before");
+            return Err(err);
+        }
+        self.notify(&block);
+        self.blocks.push(block);
+        Ok(())
+    }
+
+    /// Registers a callback run for every block that makes it onto the chain,
+    /// including the new blocks a reorg brings in.
+    pub fn on_block_added(&mut self, callback: BlockListener) {
+        self.listeners.push(callback);
+    }
+
+    fn notify(&self, block: &Block) {
+        for listener in &self.listeners {
+            listener(block);
+        }
+    }
+
+    /// The block reward at `height` plus every fee the transactions pay.
+    fn reward_for(
+        &self,
+        height: u64,
+        transactions: &[Transaction],
+    ) -> Result<u64, BlockchainError> {
+        transactions
+            .iter()
+            .try_fold(self.block_reward_at(height), |total, tx| {
+                checked_add(total, tx.fee)
+            })
+    }
+
+    /// What a block at `height` mints, before fees: the configured reward,
+    /// halved once for every `halving_interval` blocks below it until nothing
+    /// is left.
+    pub fn block_reward_at(&self, height: u64) -> u64 {
+        let halvings = height
+            .checked_div(self.config.halving_interval)
+            .unwrap_or(0);
+        u32::try_from(halvings)
+            .ok()
+            .and_then(|halvings| self.config.block_reward.checked_shr(halvings))
+            .unwrap_or(0)
+    }
+
+    /// What the coinbase of the next block mints.
+    pub fn current_reward(&self) -> u64 {
+        self.block_reward_at(self.height().saturating_add(1))
+    }
+
+    pub fn balance_of(&self, account: &Address) -> Result<u64, BlockchainError> {
+        Ok(self.utxos.balance(account))
+    }
+
+    /// The account's balance right after block `height`, found by replaying
+    /// the chain up to there. Heights past the tip give the current balance.
+    pub fn balance_of_at(&self, account: &Address, height: u64) -> u64 {
+        let mut utxos = UtxoSet::with_maturity(self.config.coinbase_maturity);
+        for block in self.iter().take_while(|block| block.id <= height) {
+            utxos.set_height(block.id);
+            for tx in &block.transactions {
+                let _ = utxos.apply(tx, self.is_issuer(&tx.origin));
+            }
+        }
+        utxos.balance(account)
+    }
+
+    /// Every account's balance from one pass over the chain: outputs are
+    /// credited and senders debited what they paid, fees included. Unlike the
+    /// UTXO set, nothing stops a balance going negative, which shows up
+    /// overspends in a chain that was not checked on the way in.
+    pub fn all_balances(&self) -> HashMap<Address, i128> {
+        let mut balances: HashMap<Address, i128> = HashMap::new();
+        for tx in self.blocks.iter().flat_map(|block| &block.transactions) {
+            for output in &tx.outputs {
+                *balances.entry(output.recipient).or_default() += i128::from(output.amount);
+            }
+            if tx.is_coinbase() {
+                continue;
+            }
+            let balance = balances.entry(tx.origin).or_default();
+            // The issuer mints what it sends without inputs
+            if !(self.is_issuer(&tx.origin) && tx.inputs.is_empty()) {
+                let quantity: i128 = tx.outputs.iter().map(|o| i128::from(o.amount)).sum();
+                *balance -= quantity + i128::from(tx.fee);
+            }
+        }
+        balances
+    }
+
+    pub fn utxo_set(&self) -> &UtxoSet {
+        &self.utxos
+    }
+
+    /// Coins minted by block rewards; fees paid to miners are not new supply.
+    pub fn total_supply(&self) -> Result<u64, BlockchainError> {
+        self.blocks.iter().try_fold(0, |supply, block| {
+            let mut minted = 0;
+            for tx in &block.transactions {
+                if tx.is_coinbase() {
+                    minted = checked_add(minted, tx.quantity()?)?;
+                } else {
+                    minted = checked_sub(minted, tx.fee)?;
+                }
+            }
+            checked_add(supply, minted)
+        })
+    }
+
+    fn transaction_bounds(&self) -> (usize, usize) {
+        (
+            self.config.min_transactions_per_block,
+            self.config.max_transactions_per_block,
+        )
+    }
+
+    /// Whether a block may hold `count` transactions besides its coinbase.
+    fn allows_transaction_count(&self, count: usize) -> bool {
+        let (min, max) = self.transaction_bounds();
+        (min..=max).contains(&count) || (count == 0 && self.config.allow_empty_blocks)
+    }
+
+    fn can_mine_pending(&self) -> bool {
+        let (min, _) = self.transaction_bounds();
+        match self.mempool.len() {
+            0 => self.config.allow_empty_blocks,
+            pending => pending >= min,
+        }
+    }
+
+    fn is_issuer(&self, account: &Address) -> bool {
+        self.config.issuer.as_ref() == Some(account)
+    }
+
+    fn check_transactions(&self, transactions: &[Transaction]) -> Result<(), BlockchainError> {
+        for tx in transactions {
+            tx.validate()?;
+            let size = tx.memo.as_ref().map_or(0, Vec::len);
+            if size > self.config.max_memo_bytes {
+                return Err(BlockchainError::MemoTooLarge {
+                    id: tx.id,
+                    size,
+                    max: self.config.max_memo_bytes,
+                });
+            }
+        }
+        let scheme = self.config.signature_algorithm.scheme();
+        if let Some(tx) = transactions
+            .iter()
+            .find(|tx| !tx.verify_signature_with(scheme))
+        {
+            return Err(BlockchainError::InvalidSignature { id: tx.id });
+        }
+        self.check_duplicates(transactions)?;
+        self.check_nonces(transactions)?;
+        self.check_spends(transactions)
+    }
+
+    /// Each origin has to count up from its last nonce on the chain, one
+    /// transaction at a time.
+    fn check_nonces(&self, transactions: &[Transaction]) -> Result<(), BlockchainError> {
+        let mut next: HashMap<&Address, u64> = HashMap::new();
+        for tx in transactions {
+            let expected = next
+                .entry(&tx.origin)
+                .or_insert_with(|| self.confirmed_nonce(&tx.origin));
+            if tx.nonce != *expected {
+                return Err(BlockchainError::BadNonce {
+                    id: tx.id,
+                    expected: *expected,
+                    got: tx.nonce,
+                });
+            }
+            *expected += 1;
+        }
+        Ok(())
+    }
+
+    fn confirmed_nonce(&self, account: &Address) -> u64 {
+        self.nonces.get(account).copied().unwrap_or(0)
+    }
+
+    /// The nonce the account's next transaction should carry, counting the
+    /// ones still waiting in the mempool.
+    pub fn next_nonce(&self, account: &Address) -> u64 {
+        let pending = self
+            .mempool
+            .transactions()
+            .iter()
+            .filter(|tx| tx.origin == *account)
+            .count() as u64;
+        self.confirmed_nonce(account) + pending
+    }
+
+    fn check_duplicates(&self, transactions: &[Transaction]) -> Result<(), BlockchainError> {
+        let mut seen = HashSet::new();
+        for tx in transactions {
+            if self.tx_index.contains_key(&tx.id) || !seen.insert(tx.id) {
+                return Err(BlockchainError::DuplicateTransaction { id: tx.id });
+            }
+        }
+        Ok(())
+    }
+
+    /// Plays the transactions, in order, on top of the UTXO set without
+    /// changing it.
+    fn check_spends(&self, transactions: &[Transaction]) -> Result<(), BlockchainError> {
+        let mut utxos = UtxoOverlay::new(&self.utxos, self.height().saturating_add(1));
+        for tx in transactions {
+            utxos.apply(tx, self.is_issuer(&tx.origin))?;
+        }
+        Ok(())
+    }
+
+    /// Target the next block has to be mined below.
+    pub fn current_target(&self) -> [u8; 32] {
+        difficulty::next_target(&self.blocks, &self.config)
+    }
+
+    /// Leading hex zeros the next block's hash is sure to have, going by
+    /// [`Blockchain::current_target`].
+    pub fn current_difficulty(&self) -> usize {
+        difficulty::target_difficulty(&self.current_target())
+    }
+
+    /// Public key of the authority whose turn it is to seal the block at
+    /// `height`, when the config names authorities.
+    pub fn expected_proposer(&self, height: u64) -> Option<&[u8]> {
+        expected_proposer(&self.config, height)
+    }
+
+    pub fn network_id(&self) -> u32 {
+        self.config.network_id
+    }
+
+    /// What blocks on this chain are hashed with, its tag included.
+    pub fn hasher(&self) -> &dyn BlockHasher {
+        self.hasher.as_ref()
+    }
+
+    pub fn config(&self) -> &BlockchainConfig {
+        &self.config
+    }
+
+    pub fn get_block_by_hash(&self, hash: &Hash) -> Option<&Block> {
+        let id = *self.hash_index.get(hash)?;
+        self.get_block_by_id(id)
+    }
+
+    pub fn find_transaction(&self, tx_id: u64) -> Option<(u64, &Transaction)> {
+        let block_id = *self.tx_index.get(&tx_id)?;
+        let tx = self
+            .get_block_by_id(block_id)?
+            .transactions
+            .iter()
+            .find(|tx| tx.id == tx_id && !tx.is_coinbase())?;
+        Some((block_id, tx))
+    }
+
+    /// Id of the block holding the transaction with this
+    /// [`Transaction::hash`].
+    pub fn block_of_transaction_hash(&self, tx_hash: &Hash) -> Option<u64> {
+        self.tx_hash_index.get(tx_hash).copied()
+    }
+
+    pub fn transaction_history(&self, account: &Address) -> Vec<&Transaction> {
+        self.transactions_for(account).map(|(_, tx)| tx).collect()
+    }
+
+    /// Transactions sent or received by `account`, with their blocks, in
+    /// chain order. Blocks are only looked at as the iterator gets to them.
+    pub fn transactions_for(
+        &self,
+        account: &Address,
+    ) -> impl Iterator<Item = (&Block, &Transaction)> + '_ {
+        let account = *account;
+        self.blocks
+            .iter()
+            .flat_map(|block| block.transactions.iter().map(move |tx| (block, tx)))
+            .filter(move |(_, tx)| tx.origin == account || tx.pays(&account))
+    }
+
+    pub fn latest_block(&self) -> Option<&Block> {
+        self.blocks.last()
+    }
+
+    /// Id of the tip; a chain holding only its genesis block has height 0.
+    pub fn height(&self) -> u64 {
+        self.latest_block().map_or(0, |block| block.id)
+    }
+
+    /// Blocks built on top of `block_id`; the tip has none. Blocks past the
+    /// tip have none either.
+    pub fn confirmations(&self, block_id: u64) -> u64 {
+        self.height().saturating_sub(block_id)
+    }
+
+    /// Whether the block is on the chain with at least `min_depth` blocks on
+    /// top of it.
+    pub fn is_confirmed(&self, block_id: u64, min_depth: u64) -> bool {
+        block_id <= self.height() && self.confirmations(block_id) >= min_depth
+    }
+
+    /// Confirmations of the block holding the transaction, if it is on the
+    /// chain.
+    pub fn transaction_confirmations(&self, tx_id: u64) -> Option<u64> {
+        let block_id = *self.tx_index.get(&tx_id)?;
+        Some(self.confirmations(block_id))
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Block> {
+        self.blocks.iter()
+    }
+
+    pub fn get_block_by_id(&self, id: u64) -> Option<&Block> {
+        let index = usize::try_from(id).ok()?;
+        self.blocks.get(index)
+    }
+
+    /// Blocks `from` to `to`, both included, cut down to the ones the chain
+    /// has.
+    pub fn get_blocks(&self, from: u64, to: u64) -> Vec<&Block> {
+        let skipped = usize::try_from(from).unwrap_or(usize::MAX);
+        self.blocks
+            .iter()
+            .skip(skipped)
+            .take_while(|block| block.id <= to)
+            .collect()
+    }
+
+    /// Mean number of seconds between consecutive blocks.
+    pub fn average_block_time(&self) -> Option<f64> {
+        let [first, .., last] = self.blocks.as_slice() else {
+            return None;
+        };
+        let intervals = (self.blocks.len() - 1) as f64;
+        Some(last.timestamp.saturating_sub(first.timestamp) as f64 / intervals)
+    }
+
+    /// Seconds between the timestamps of two blocks, in either order.
+    pub fn block_time_between(&self, id_a: u64, id_b: u64) -> Option<u64> {
+        let a = self.get_block_by_id(id_a)?;
+        let b = self.get_block_by_id(id_b)?;
+        Some(a.timestamp.abs_diff(b.timestamp))
+    }
+
+    /// Hashes per second the network spent on the last `window` blocks. A
+    /// block mined below target `T` takes 2^256 / (T + 1) hashes on average,
+    /// and the work of the oldest block in the window was done before the
+    /// window started.
+    pub fn estimated_hashrate(&self, window: usize) -> Option<f64> {
+        if window < 2 || window > self.blocks.len() {
+            return None;
+        }
+        let recent = &self.blocks[self.blocks.len() - window..];
+        let elapsed = recent[window - 1]
+            .timestamp
+            .saturating_sub(recent[0].timestamp);
+        if elapsed == 0 {
+            return None;
+        }
+        let work: f64 = recent[1..]
+            .iter()
+            .map(|block| difficulty::target_work(&block.target) as f64)
+            .sum();
+        Some(work / elapsed as f64)
+    }
+
+    /// Trusts every block up to `height` as long as the block there has this
+    /// hash, so validation only has to look at the blocks above it. A chain
+    /// that has not reached the checkpoint yet is validated in full.
+    pub fn set_checkpoint(&mut self, height: u64, hash: Hash) {
+        self.checkpoint = Some((height, hash));
+    }
+
+    pub fn validate_chain(&self) -> bool {
+        self.validate_chain_detailed().is_ok()
+    }
+
+    pub fn validate_chain_detailed(&self) -> Result<(), ValidationError> {
+        self.validate_blocks(&self.blocks)
+    }
+
+    /// Where the chain stops being valid, e.g. the first block someone has
+    /// edited. `None` when every block checks out.
+    pub fn first_invalid_block(&self) -> Option<u64> {
+        self.validate_chain_detailed()
+            .err()
+            .and_then(|err| err.block_id())
+    }
+
+    pub fn headers(&self) -> Vec<BlockHeader> {
+        self.blocks.iter().map(Block::header).collect()
+    }
+
+    /// Headers of the blocks above `from` up to `to`, for a client that
+    /// trusts the header at `from` to check with [`crate::verify_chain_proof`].
+    /// Empty unless both heights are on the chain and `from` is below `to`.
+    pub fn chain_proof(&self, from: u64, to: u64) -> Vec<BlockHeader> {
+        if from >= to || to > self.height() {
+            return Vec::new();
+        }
+        self.get_blocks(from + 1, to)
+            .into_iter()
+            .map(Block::header)
+            .collect()
+    }
+
+    pub fn summaries(&self) -> Vec<BlockSummary> {
+        self.blocks.iter().map(Block::summary).collect()
+    }
+
+    /// Checks a header chain from genesis up without the transactions: every
+    /// header is on this network, meets its difficulty and links to the hash
+    /// of the one before it. Difficulty retargets and transaction counts are
+    /// left for when the bodies arrive.
+    pub fn validate_headers(&self, headers: &[BlockHeader]) -> Result<(), ValidationError> {
+        if headers.is_empty() {
+            return Err(ValidationError::EmptyChain);
+        }
+
+        let mut previous: Option<(Hash, u64)> = None;
+        for (index, header) in (0..).zip(headers) {
+            let block_id = header.id;
+            if index == 0 && (block_id != 0 || header.previous_hash != Hash::ZERO) {
+                return Err(ValidationError::InvalidGenesis);
+            }
+            if block_id != index {
+                return Err(ValidationError::UnexpectedId {
+                    block_id,
+                    expected: index,
+                });
+            }
+            if header.network_id != self.config.network_id {
+                return Err(ValidationError::WrongNetwork {
+                    block_id,
+                    network_id: header.network_id,
+                });
+            }
+
+            let hash = header.calculate_hash_with(self.hasher.as_ref());
+            if index == 0 && !is_expected_genesis(Some(hash), &self.config) {
+                return Err(ValidationError::InvalidGenesis);
+            }
+            if !header.meets_target(&hash) {
+                return Err(ValidationError::InsufficientWork { block_id });
+            }
+            if let Some((previous_hash, previous_timestamp)) = previous {
+                if header.previous_hash != previous_hash {
+                    return Err(ValidationError::BrokenLink { block_id });
+                }
+                if header.timestamp < previous_timestamp {
+                    return Err(ValidationError::TimestampRegression { block_id });
+                }
+            }
+            previous = Some((hash, header.timestamp));
+        }
+        Ok(())
+    }
+
+    /// Highest block id at which both chains hold the same block.
+    pub fn common_ancestor(&self, other: &Blockchain) -> Option<u64> {
+        shared_prefix_len(&self.blocks, &other.blocks)
+            .checked_sub(1)
+            .map(|len| len as u64)
+    }
+
+    /// First block id at which the chains differ, counting a block that only
+    /// one of them has. `None` means they are identical.
+    pub fn divergence_point(&self, other: &Blockchain) -> Option<u64> {
+        let shared = shared_prefix_len(&self.blocks, &other.blocks);
+        (shared < self.blocks.len().max(other.blocks.len())).then_some(shared as u64)
+    }
+
+    /// Hashes it took, on average, to mine every block on the chain, going by
+    /// the target each one was mined below.
+    pub fn total_work(&self) -> u128 {
+        total_work(&self.blocks)
+    }
+
+    /// Switches to `candidate` if it is valid and holds more work than the
+    /// chain we have, whatever its length. The blocks past the ones we share
+    /// go through [`Blockchain::check_block`] one after the other, so their
+    /// transactions, rewards and spends have to hold up too; if one does
+    /// not, the chain is left as it was. Forks below the prune height are
+    /// refused.
+    pub fn try_replace_chain(&mut self, candidate: Vec<Block>) -> Result<bool, BlockchainError> {
+        if total_work(&candidate) <= self.total_work() {
+            return Ok(false);
+        }
+
+        let local_genesis = self.blocks.first().ok_or(BlockchainError::EmptyChain)?;
+        if candidate[0].hash != local_genesis.hash {
+            return Err(BlockchainError::GenesisMismatch);
+        }
+        let shared = shared_prefix_len(&self.blocks, &candidate);
+        if (shared as u64) < self.pruned_below {
+            return Err(BlockchainError::PrunedHistory {
+                height: self.pruned_below,
+            });
+        }
+        for block in &candidate[shared..] {
+            check_unpruned(block).map_err(BlockchainError::InvalidChain)?;
+        }
+
+        // Every block is checked again, even the ones matching our own prefix
+        self.validate_blocks(&candidate)
+            .map_err(BlockchainError::InvalidChain)?;
+
+        // Our own copies of the shared blocks stay, pruned or not
+        let abandoned = self.blocks.split_off(shared);
+        self.reindex().expect("these blocks were indexed before");
+        if let Err(err) = self.append_checked(candidate.into_iter().skip(shared)) {
+            self.blocks.truncate(shared);
+            self.blocks.extend(abandoned);
+            self.reindex().expect("these blocks were indexed before");
+            return Err(err);
+        }
+        for block in &self.blocks[shared..] {
+            if let Some(hash) = block.hash {
+                self.branches.remove(&hash);
+            }
+        }
+        for block in abandoned {
+            if let Some(hash) = block.hash {
+                self.branches.insert(hash, block);
+            }
+        }
+        for block in &self.blocks[shared..] {
+            self.notify(block);
+        }
+        Ok(true)
+    }
+
+    fn validate_blocks(&self, blocks: &[Block]) -> Result<(), ValidationError> {
+        if blocks.is_empty() {
+            return Err(ValidationError::EmptyChain);
+        }
+        let trusted = match self.checkpoint {
+            Some((height, hash)) => {
+                match usize::try_from(height).ok().and_then(|i| blocks.get(i)) {
+                    Some(block) if block.hash == Some(hash) => Some(height),
+                    Some(_) => {
+                        return Err(ValidationError::CheckpointMismatch { block_id: height })
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+
+        let mut validator = BlockValidator::new(self, trusted);
+        for block in blocks {
+            validator.check(block)?;
+        }
+        Ok(())
+    }
+
+    /// Validates blocks as they arrive, starting from genesis, without holding
+    /// on to them. Checkpoints are not used since the stream may end before
+    /// reaching one.
+    pub fn validate_stream(
+        &self,
+        blocks: impl Iterator<Item = Block>,
+    ) -> Result<(), ValidationError> {
+        let mut validator = BlockValidator::new(self, None);
+        for block in blocks {
+            validator.check(&block)?;
+        }
+        validator.finish()
+    }
+}
+
+/// What a block is checked against: the one before it and the timestamp at
+/// the start of the current retarget window.
+struct BlockValidator<'a> {
+    blockchain: &'a Blockchain,
+    trusted: Option<u64>,
+    latest_allowed: u64,
+    next_index: u64,
+    previous: Option<(Option<Hash>, u64, [u8; 32])>,
+    window: VecDeque<u64>,
+}
+
+impl<'a> BlockValidator<'a> {
+    fn new(blockchain: &'a Blockchain, trusted: Option<u64>) -> Self {
+        Self {
+            blockchain,
+            trusted,
+            latest_allowed: blockchain
+                .clock
+                .now_secs()
+                .saturating_add(blockchain.config.max_future_drift_secs),
+            next_index: 0,
+            previous: None,
+            window: VecDeque::new(),
+        }
+    }
+
+    /// Picks up after the chain's current tip.
+    fn after(blockchain: &'a Blockchain) -> Self {
+        let blocks = &blockchain.blocks;
+        let window = (blockchain.config.target_adjustment_interval as usize).saturating_add(1);
+        let start = blocks.len().saturating_sub(window);
+
+        let mut validator = Self::new(blockchain, None);
+        validator.next_index = start as u64;
+        for block in &blocks[start..] {
+            validator.record(block);
+        }
+        validator
+    }
+
+    fn check(&mut self, block: &Block) -> Result<(), ValidationError> {
+        let index = self.next_index;
+        if self.trusted.is_none_or(|height| index > height) {
+            self.check_untrusted(index, block)?;
+        }
+        self.record(block);
+        Ok(())
+    }
+
+    fn record(&mut self, block: &Block) {
+        self.previous = Some((block.hash, block.timestamp, block.target));
+        self.window.push_back(block.timestamp);
+        if self.window.len() as u64 > self.blockchain.config.target_adjustment_interval + 1 {
+            self.window.pop_front();
+        }
+        self.next_index += 1;
+    }
+
+    fn check_untrusted(&self, index: u64, block: &Block) -> Result<(), ValidationError> {
+        let block_id = block.id;
+        if index == 0 && (block_id != 0 || block.previous_hash != Hash::ZERO) {
+            return Err(ValidationError::InvalidGenesis);
+        }
+        if block_id != index {
+            return Err(ValidationError::UnexpectedId {
+                block_id,
+                expected: index,
+            });
+        }
+
+        check_network(block, &self.blockchain.config)?;
+        if block.is_pruned() {
+            if !block.transactions.is_empty() {
+                return Err(ValidationError::PrunedWithTransactions { block_id });
+            }
+            if block_id >= self.blockchain.pruned_below {
+                return Err(ValidationError::UnexpectedPrune { block_id });
+            }
+        }
+        block.verify_hash_with(self.blockchain.hasher.as_ref())?;
+        if index == 0 && !is_expected_genesis(block.hash, &self.blockchain.config) {
+            return Err(ValidationError::InvalidGenesis);
+        }
+
+        if !block.meets_difficulty() {
+            return Err(ValidationError::InsufficientWork { block_id });
+        }
+        // The genesis block is fixed by the config rather than sealed
+        if index > 0 {
+            if !self.blockchain.consensus.verify(block) {
+                return Err(ValidationError::InvalidSeal { block_id });
+            }
+            check_proposer(block, &self.blockchain.config)?;
+        }
+
+        if let Some((previous_hash, previous_timestamp, previous_target)) = self.previous {
+            // The coinbase does not count towards the bounds
+            let count = block.transactions.len().saturating_sub(1);
+            if !block.is_pruned() && !self.blockchain.allows_transaction_count(count) {
+                return Err(ValidationError::WrongTransactionCount { block_id, count });
+            }
+
+            let config = &self.blockchain.config;
+            let window_start = (self.window.len() as u64 > config.target_adjustment_interval)
+                .then(|| self.window[0]);
+            let target = difficulty::target_after(
+                index - 1,
+                &previous_target,
+                previous_timestamp,
+                window_start,
+                config,
+            );
+            let expected = difficulty::target_difficulty(&target);
+            if block.difficulty != expected {
+                return Err(ValidationError::UnexpectedDifficulty { block_id, expected });
+            }
+            if block.target != target {
+                return Err(ValidationError::UnexpectedTarget { block_id });
+            }
+
+            if previous_hash != Some(block.previous_hash) {
+                return Err(ValidationError::BrokenLink { block_id });
+            }
+            if block.timestamp < previous_timestamp {
+                return Err(ValidationError::TimestampRegression { block_id });
+            }
+        }
+
+        if block.timestamp > self.latest_allowed {
+            return Err(ValidationError::FutureTimestamp { block_id });
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), ValidationError> {
+        if self.next_index == 0 {
+            return Err(ValidationError::EmptyChain);
+        }
+        Ok(())
+    }
+}
+
+/// An unsealed genesis block with one coinbase per initial allocation.
+fn genesis_block(config: &BlockchainConfig, clock: &dyn Clock) -> Block {
+    let mut genesis = Block::new_with_clock(0, Hash::ZERO, clock);
+    genesis.network_id = config.network_id;
+    genesis.transactions = config
+        .initial_allocations
+        .iter()
+        .zip(0..)
+        .map(|(&(account, amount), index)| Transaction::allocation(index, account, amount))
+        .collect();
+    genesis
+}
+
+/// Whether a genesis block with this hash is the one the config names, if it
+/// names one.
+fn is_expected_genesis(hash: Option<Hash>, config: &BlockchainConfig) -> bool {
+    config
+        .genesis_hash
+        .is_none_or(|expected| hash == Some(expected))
+}
+
+fn check_network(block: &Block, config: &BlockchainConfig) -> Result<(), ValidationError> {
+    if block.network_id != config.network_id {
+        return Err(ValidationError::WrongNetwork {
+            block_id: block.id,
+            network_id: block.network_id,
+        });
+    }
+    Ok(())
+}
+
+/// Blocks from elsewhere have to bring their transactions along; a pruned
+/// root is only trusted for the blocks pruned here.
+fn check_unpruned(block: &Block) -> Result<(), ValidationError> {
+    if block.is_pruned() {
+        return Err(ValidationError::UnexpectedPrune { block_id: block.id });
+    }
+    Ok(())
+}
+
+/// Under a config naming authorities, the block at height `h` has to be sealed
+/// by `authorities[h % len]`.
+fn check_proposer(block: &Block, config: &BlockchainConfig) -> Result<(), ValidationError> {
+    let Some(expected) = expected_proposer(config, block.id) else {
+        return Ok(());
+    };
+    if block.seal.as_ref().map(|seal| seal.signer.as_slice()) != Some(expected) {
+        return Err(ValidationError::WrongProposer { block_id: block.id });
+    }
+    Ok(())
+}
+
+fn expected_proposer(config: &BlockchainConfig, height: u64) -> Option<&[u8]> {
+    let count = config.authorities.len() as u64;
+    if count == 0 {
+        return None;
+    }
+    Some(&config.authorities[(height % count) as usize])
+}
+
+fn total_work(blocks: &[Block]) -> u128 {
+    blocks.iter().fold(0, |total, block| {
+        total.saturating_add(difficulty::target_work(&block.target))
+    })
+}
+
+/// Number of leading blocks the two chains have in common.
+fn shared_prefix_len(ours: &[Block], theirs: &[Block]) -> usize {
+    ours.iter()
+        .zip(theirs)
+        .take_while(|(ours, theirs)| ours.hash == theirs.hash)
+        .count()
+}
+
+fn check_time_window(transactions: &[Transaction], timestamp: u64) -> Result<(), BlockchainError> {
+    for tx in transactions {
+        if let Some(not_before) = tx.not_before.filter(|&time| time > timestamp) {
+            return Err(BlockchainError::TimeLocked {
+                id: tx.id,
+                not_before,
+            });
+        }
+        if let Some(expires_at) = tx.expires_at.filter(|_| tx.is_expired(timestamp)) {
+            return Err(BlockchainError::Expired {
+                id: tx.id,
+                expires_at,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn checked_add(left: u64, right: u64) -> Result<u64, BlockchainError> {
+    left.checked_add(right).ok_or(BlockchainError::Overflow)
+}
+
+fn checked_sub(left: u64, right: u64) -> Result<u64, BlockchainError> {
+    left.checked_sub(right).ok_or(BlockchainError::Overflow)
+}
+
+impl<'a> IntoIterator for &'a Blockchain {
+    type Item = &'a Block;
+    type IntoIter = std::slice::Iter<'a, Block>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new(BlockchainConfig::default())
+    }
+}
+
+impl fmt::Display for Blockchain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for block in &self.blocks {
+            writeln!(f, "{}", block)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::verify_chain_proof;
+    use crate::clock::MockClock;
+    use crate::consensus::{ProofOfAuthority, ProofOfWork};
+    use crate::signature::SignatureAlgorithm;
+    use crate::test_support::{
+        address, chain, config, issue, issuer, miner, transfer, transfer_with_fee,
+        transfer_with_nonce, wallet,
+    };
+    use crate::utxo::{TxInput, TxOutput};
+    use crate::wallet::Wallet;
+
+    fn sample_transactions() -> Vec<Transaction> {
+        issue(1..=5)
+    }
+
+    #[test]
+    fn test_blockchain_creation() {
+        let blockchain = Blockchain::default();
+        assert!(blockchain.get_block_by_id(0).is_some());
+    }
+
+    #[test]
+    fn test_blockchain_addition() {
+        let mut blockchain = chain();
+        let id = blockchain
+            .add_block(sample_transactions(), miner())
+            .unwrap();
+        assert_eq!(id, 1);
+        assert!(blockchain.get_block_by_id(1).is_some());
+    }
+
+    #[test]
+    fn test_wrong_transaction_count_is_rejected() {
+        let mut blockchain = chain();
+
+        assert_eq!(
+            blockchain.add_block(Vec::new(), miner()),
+            Err(BlockchainError::WrongTransactionCount {
+                min: 1,
+                max: 5,
+                got: 0
+            })
+        );
+        assert_eq!(
+            blockchain.add_block(issue(1..=6), miner()),
+            Err(BlockchainError::WrongTransactionCount {
+                min: 1,
+                max: 5,
+                got: 6
+            })
+        );
+        assert!(blockchain.get_block_by_id(1).is_none());
+    }
+
+    #[test]
+    fn test_missing_previous_hash_is_rejected() {
+        let mut blockchain = chain();
+        blockchain.blocks[0].hash = None;
+
+        assert_eq!(
+            blockchain.add_block(sample_transactions(), miner()),
+            Err(BlockchainError::MissingPreviousHash)
+        );
+    }
+
+    #[test]
+    fn test_empty_chain_is_rejected() {
+        let mut blockchain = chain();
+        blockchain.blocks.clear();
+
+        assert_eq!(
+            blockchain.add_block(sample_transactions(), miner()),
+            Err(BlockchainError::EmptyChain)
+        );
+    }
+
+    #[test]
+    fn test_chain_validation() {
+        let mut blockchain = chain();
+        blockchain
+            .add_block(sample_transactions(), miner())
+            .unwrap();
+
+        assert!(blockchain.validate_chain());
+
+        // Tamper with the blockchain
+        let tampered_block = &mut blockchain.blocks[1];
+        tampered_block.transactions[0].outputs[0].amount = 100;
+
+        assert!(!blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_configurable_block_capacity() {
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            max_transactions_per_block: 3,
+            ..config()
+        });
+
+        let id = blockchain.add_block(issue(1..=3), miner()).unwrap();
+        let block = blockchain.get_block_by_id(id).unwrap();
+        assert_eq!(block.transactions.len(), 4);
+        assert!(block.hash.is_some());
+        assert!(blockchain.validate_chain());
+
+        assert_eq!(
+            blockchain.add_block(sample_transactions(), miner()),
+            Err(BlockchainError::WrongTransactionCount {
+                min: 1,
+                max: 3,
+                got: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_block_finalizes_at_capacity() {
+        let mut block = Block::with_capacity(1, Hash::ZERO, 3);
+        for transaction in issue(1..=2) {
+            block.add_transaction(transaction);
+        }
+        assert!(block.hash.is_none());
+
+        block.add_transaction(issue([3]).remove(0));
+        assert!(block.hash.is_some());
+
+        block.add_transaction(issue([4]).remove(0));
+        assert_eq!(block.transactions.len(), 3);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            difficulty: 1,
+            ..config()
+        });
+        for block in 0..3 {
+            let first = block * 5 + 1;
+            blockchain
+                .add_block(issue(first..=first + 4), miner())
+                .unwrap();
+        }
+
+        let json = serde_json::to_string(&blockchain).unwrap();
+        let restored: Blockchain = serde_json::from_str(&json).unwrap();
+
+        assert!(restored.validate_chain());
+        assert_eq!(restored.blocks.len(), blockchain.blocks.len());
+        for (original, copy) in blockchain.blocks.iter().zip(&restored.blocks) {
+            assert_eq!(copy.hash, original.hash);
+            assert_eq!(
+                copy.transactions.iter().map(|tx| tx.id).collect::<Vec<_>>(),
+                original
+                    .transactions
+                    .iter()
+                    .map(|tx| tx.id)
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_mined_chain_validation() {
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            difficulty: 2,
+            ..config()
+        });
+        blockchain
+            .add_block(sample_transactions(), miner())
+            .unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+
+        assert!(blockchain.validate_chain());
+        assert!(blockchain.blocks[2].meets_difficulty());
+        assert!(blockchain.blocks[2].hash.unwrap().leading_zero_nibbles() >= 2);
+
+        // Claiming more work than was actually done must be caught
+        let block = &mut blockchain.blocks[2];
+        block.difficulty = 64;
+        block.hash = Some(block.calculate_hash());
+
+        assert!(!blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_get_block_by_id_out_of_range() {
+        let blockchain = Blockchain::default();
+        assert!(blockchain.get_block_by_id(1).is_none());
+        assert!(blockchain.get_block_by_id(u64::MAX).is_none());
+    }
+
+    #[test]
+    fn test_validate_large_chain() {
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            min_transactions_per_block: 0,
+            target_adjustment_interval: 0,
+            ..BlockchainConfig::default()
+        });
+        for id in 1..=10_000 {
+            let previous_hash = blockchain.blocks.last().unwrap().hash.unwrap();
+            let mut block = Block::new(id, previous_hash);
+            block.hash = Some(block.calculate_hash());
+            blockchain.blocks.push(block);
+        }
+
+        assert_eq!(blockchain.blocks.len(), 10_001);
+        assert!(blockchain.validate_chain());
+
+        blockchain.blocks[5_000].previous_hash = Hash::ZERO;
+        assert!(!blockchain.validate_chain());
+    }
+
+    fn two_per_block() -> Blockchain {
+        Blockchain::new(BlockchainConfig {
+            max_transactions_per_block: 2,
+            ..config()
+        })
+    }
+
+    #[test]
+    fn test_balances_follow_transfers() {
+        let (alice, bob) = (wallet(1), wallet(2));
+        let mut blockchain = two_per_block();
+        blockchain
+            .add_block(
+                vec![
+                    transfer(1, &issuer(), &alice.address(), 100),
+                    transfer(2, &alice, &bob.address(), 60),
+                ],
+                miner(),
+            )
+            .unwrap();
+
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(40));
+        assert_eq!(blockchain.balance_of(&bob.address()), Ok(60));
+        assert_eq!(blockchain.balance_of(&wallet(3).address()), Ok(0));
+    }
+
+    #[test]
+    fn test_overspending_is_rejected() {
+        let (alice, bob) = (wallet(1), wallet(2));
+        let mut blockchain = two_per_block();
+        blockchain
+            .add_block(
+                vec![
+                    transfer(1, &issuer(), &alice.address(), 40),
+                    transfer_with_nonce(2, 1, &issuer(), &bob.address(), 10),
+                ],
+                miner(),
+            )
+            .unwrap();
+
+        let result = blockchain.add_block(
+            vec![
+                transfer(3, &alice, &bob.address(), 50),
+                transfer(4, &bob, &alice.address(), 5),
+            ],
+            miner(),
+        );
+        assert_eq!(
+            result,
+            Err(BlockchainError::InsufficientFunds {
+                account: alice.address(),
+                available: 40,
+                required: 50,
+            })
+        );
+        assert_eq!(blockchain.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_double_spend_within_block_is_rejected() {
+        let (alice, bob, carol) = (wallet(1), wallet(2), wallet(3));
+        let mut blockchain = two_per_block();
+        blockchain
+            .add_block(
+                vec![
+                    transfer(1, &issuer(), &alice.address(), 40),
+                    transfer_with_nonce(2, 1, &issuer(), &bob.address(), 10),
+                ],
+                miner(),
+            )
+            .unwrap();
+
+        let result = blockchain.add_block(
+            vec![
+                transfer(3, &alice, &bob.address(), 30),
+                transfer_with_nonce(4, 1, &alice, &carol.address(), 30),
+            ],
+            miner(),
+        );
+        assert_eq!(
+            result,
+            Err(BlockchainError::InsufficientFunds {
+                account: alice.address(),
+                available: 10,
+                required: 30,
+            })
+        );
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(40));
+    }
+
+    #[test]
+    fn test_would_accept_leaves_the_mempool_alone() {
+        let alice = wallet(1);
+        let mut blockchain = chain();
+        blockchain
+            .submit_transaction(transfer(1, &issuer(), &alice.address(), 40))
+            .unwrap();
+
+        // The pending funding counts, so spending it all is fine
+        let spend = transfer(2, &alice, &address("Bob"), 40);
+        assert_eq!(blockchain.would_accept(&spend), Ok(()));
+
+        let overspend = transfer(2, &alice, &address("Bob"), 50);
+        let expected = Err(BlockchainError::InsufficientFunds {
+            account: alice.address(),
+            available: 40,
+            required: 50,
+        });
+        assert_eq!(blockchain.would_accept(&overspend), expected);
+        assert_eq!(blockchain.pending_count(), 1);
+
+        assert_eq!(
+            blockchain.submit_transaction(overspend).map(|_| ()),
+            expected
+        );
+        assert_eq!(blockchain.pending_count(), 1);
+    }
+
+    #[test]
+    fn test_mempool_assembles_full_blocks() {
+        let mut blockchain = chain();
+        blockchain.set_miner(miner());
+        let mut transactions = sample_transactions().into_iter();
+
+        for tx in transactions.by_ref().take(4) {
+            assert_eq!(blockchain.submit_transaction(tx), Ok(None));
+        }
+        assert_eq!(blockchain.pending_count(), 4);
+        assert_eq!(blockchain.blocks.len(), 1);
+
+        let last = transactions.next().unwrap();
+        assert_eq!(blockchain.submit_transaction(last), Ok(Some(1)));
+        assert_eq!(blockchain.pending_count(), 0);
+        assert_eq!(blockchain.get_block_by_id(1).unwrap().transactions.len(), 6);
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_flush_pending_builds_partial_block() {
+        let mut blockchain = chain();
+        assert_eq!(blockchain.flush_pending(miner()), Ok(None));
+
+        for tx in issue(1..=2) {
+            blockchain.submit_transaction(tx).unwrap();
+        }
+        assert_eq!(
+            blockchain
+                .pending_transactions()
+                .iter()
+                .map(|tx| tx.id)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        assert_eq!(blockchain.flush_pending(miner()), Ok(Some(1)));
+        assert_eq!(blockchain.pending_count(), 0);
+        assert_eq!(blockchain.get_block_by_id(1).unwrap().transactions.len(), 3);
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_mempool_rejects_unfunded_transaction() {
+        let (alice, bob) = (wallet(1), wallet(2));
+        let mut blockchain = chain();
+        blockchain
+            .submit_transaction(transfer(1, &issuer(), &alice.address(), 10))
+            .unwrap();
+
+        assert!(matches!(
+            blockchain.submit_transaction(transfer(2, &alice, &bob.address(), 20)),
+            Err(BlockchainError::InsufficientFunds { .. })
+        ));
+        assert_eq!(blockchain.pending_count(), 1);
+    }
+
+    #[test]
+    fn test_forged_signature_is_rejected() {
+        let (alice, mallory) = (wallet(1), wallet(2));
+        let mut blockchain = two_per_block();
+        blockchain
+            .add_block(
+                vec![
+                    transfer(1, &issuer(), &alice.address(), 100),
+                    transfer_with_nonce(2, 1, &issuer(), &mallory.address(), 1),
+                ],
+                miner(),
+            )
+            .unwrap();
+
+        let mut forged = transfer(3, &mallory, &mallory.address(), 100);
+        forged.origin = alice.address();
+        let result = blockchain.add_block(
+            vec![forged, transfer(4, &mallory, &alice.address(), 1)],
+            miner(),
+        );
+
+        assert_eq!(result, Err(BlockchainError::InvalidSignature { id: 3 }));
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(100));
+    }
+
+    #[test]
+    fn test_mempool_waits_for_a_miner() {
+        let mut blockchain = chain();
+        for tx in sample_transactions() {
+            assert_eq!(blockchain.submit_transaction(tx), Ok(None));
+        }
+        assert_eq!(blockchain.pending_count(), 5);
+
+        assert_eq!(blockchain.flush_pending(miner()), Ok(Some(1)));
+        assert_eq!(blockchain.balance_of(&miner()), Ok(50));
+    }
+
+    #[test]
+    fn test_coinbase_rewards_the_miner() {
+        let mut blockchain = chain();
+        blockchain
+            .add_block(sample_transactions(), miner())
+            .unwrap();
+        blockchain
+            .add_block(issue(6..=10), wallet(1).address())
+            .unwrap();
+
+        let coinbase = &blockchain.blocks[1].transactions[0];
+        assert!(coinbase.is_coinbase());
+        assert_eq!(coinbase.outputs[0].recipient, miner());
+        assert_eq!(coinbase.outputs[0].amount, 50);
+
+        assert_eq!(blockchain.balance_of(&miner()), Ok(50));
+        assert_eq!(blockchain.balance_of(&wallet(1).address()), Ok(50));
+        assert_eq!(blockchain.total_supply(), Ok(100));
+    }
+
+    #[test]
+    fn test_coinbase_reward_is_spendable() {
+        let (alice, bob) = (wallet(1), wallet(2));
+        let mut blockchain = two_per_block();
+        blockchain.add_block(issue(1..=2), alice.address()).unwrap();
+
+        blockchain
+            .add_block(
+                vec![
+                    transfer(3, &alice, &bob.address(), 30),
+                    transfer_with_nonce(4, 1, &alice, &bob.address(), 20),
+                ],
+                miner(),
+            )
+            .unwrap();
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(0));
+        assert_eq!(blockchain.balance_of(&bob.address()), Ok(50));
+    }
+
+    #[test]
+    fn test_coinbase_is_part_of_block_hash() {
+        let mut blockchain = chain();
+        blockchain
+            .add_block(sample_transactions(), miner())
+            .unwrap();
+        let root = blockchain.blocks[1].merkle_root();
+
+        blockchain.blocks[1].transactions[0].outputs[0].amount = 5_000;
+        assert_ne!(blockchain.blocks[1].merkle_root(), root);
+        assert!(!blockchain.validate_chain());
+    }
+
+    fn retargeting_chain() -> Blockchain {
+        Blockchain::new(BlockchainConfig {
+            difficulty: 1,
+            target_block_time_secs: 60,
+            target_adjustment_interval: 2,
+            ..config()
+        })
+    }
+
+    #[test]
+    fn test_difficulty_is_kept_early_in_the_chain() {
+        let mut blockchain = retargeting_chain();
+        assert_eq!(blockchain.current_difficulty(), 1);
+
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        assert_eq!(blockchain.current_difficulty(), 1);
+        assert_eq!(blockchain.blocks[1].difficulty, 1);
+    }
+
+    #[test]
+    fn test_fast_blocks_raise_difficulty() {
+        let mut blockchain = retargeting_chain();
+        assert_eq!(blockchain.current_target()[0], 0x10);
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+
+        // Both blocks were mined well inside the two minute target window, so
+        // the target drops to a quarter, the most one retarget allows. That is
+        // not a whole hex zero yet.
+        let mut quarter = [0u8; 32];
+        quarter[0] = 0x04;
+        assert_eq!(blockchain.current_target(), quarter);
+        assert_eq!(blockchain.current_difficulty(), 1);
+        blockchain.add_block(issue(11..=15), miner()).unwrap();
+        blockchain.add_block(issue(16..=20), miner()).unwrap();
+        assert_eq!(blockchain.blocks[3].target, quarter);
+
+        // The next quarter is
+        assert_eq!(blockchain.current_difficulty(), 2);
+        blockchain.add_block(issue(21..=25), miner()).unwrap();
+        assert_eq!(blockchain.blocks[5].target[..2], [0x01, 0x00]);
+        assert_eq!(blockchain.blocks[5].difficulty, 2);
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_block_with_unexpected_difficulty_is_invalid() {
+        let mut blockchain = retargeting_chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+        blockchain.add_block(issue(11..=15), miner()).unwrap();
+
+        blockchain.blocks[3].mine(0);
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::UnexpectedDifficulty {
+                block_id: 3,
+                expected: 1
+            })
+        );
+
+        // The right number of hex zeros, but not the retargeted target
+        blockchain.blocks[3].mine(1);
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::UnexpectedTarget { block_id: 3 })
+        );
+    }
+
+    fn fork(blockchain: &Blockchain) -> Blockchain {
+        Blockchain::from_parts(blockchain.blocks.clone(), blockchain.config.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_longer_fork_replaces_chain() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+
+        let mut peer = fork(&local);
+        local.add_block(issue(6..=10), miner()).unwrap();
+        peer.add_block(issue(6..=10), wallet(1).address()).unwrap();
+        peer.add_block(issue(11..=15), wallet(1).address()).unwrap();
+
+        assert_eq!(local.try_replace_chain(peer.blocks.clone()), Ok(true));
+        assert_eq!(local.blocks.len(), 4);
+        assert_eq!(local.blocks[3].hash, peer.blocks[3].hash);
+        assert!(local.validate_chain());
+    }
+
+    #[test]
+    fn test_shorter_or_equal_fork_is_ignored() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let peer = fork(&local);
+        local.add_block(issue(6..=10), miner()).unwrap();
+
+        assert_eq!(local.try_replace_chain(peer.blocks.clone()), Ok(false));
+        assert_eq!(local.try_replace_chain(local.blocks.clone()), Ok(false));
+        assert_eq!(local.blocks.len(), 3);
+    }
+
+    #[test]
+    fn test_fork_with_tampered_shared_prefix_is_rejected() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+
+        let mut peer = fork(&local);
+        peer.add_block(issue(6..=10), miner()).unwrap();
+        peer.add_block(issue(11..=15), miner()).unwrap();
+        peer.blocks[1].transactions[1].outputs[0].amount += 1;
+
+        assert_eq!(
+            local.try_replace_chain(peer.blocks.clone()),
+            Err(BlockchainError::InvalidChain(
+                ValidationError::HashMismatch { block_id: 1 }
+            ))
+        );
+        assert_eq!(local.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_fork_from_another_genesis_is_rejected() {
+        let mut local = chain();
+        let mut other = chain();
+        other.blocks[0].timestamp += 1;
+        other.blocks[0].hash = Some(other.blocks[0].calculate_hash());
+        other.add_block(issue(1..=5), miner()).unwrap();
+
+        assert_eq!(
+            local.try_replace_chain(other.blocks.clone()),
+            Err(BlockchainError::GenesisMismatch)
+        );
+    }
+
+    #[test]
+    fn test_iter_yields_blocks_in_order() {
+        let mut blockchain = chain();
+        assert_eq!(
+            blockchain.iter().map(|block| block.id).collect::<Vec<_>>(),
+            vec![0]
+        );
+
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+
+        let mut ids = Vec::new();
+        for block in &blockchain {
+            ids.push(block.id);
+        }
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert_eq!(blockchain.iter().last().unwrap().id, 2);
+    }
+
+    #[test]
+    fn test_get_block_by_hash() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+        blockchain.add_block(issue(11..=15), miner()).unwrap();
+
+        for block in &blockchain {
+            let found = blockchain.get_block_by_hash(&block.hash.unwrap()).unwrap();
+            assert_eq!(found.id, block.id);
+        }
+        assert!(blockchain.get_block_by_hash(&Hash::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_hash_index_survives_round_trip_and_reorg() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let mut peer = fork(&local);
+        local.add_block(issue(6..=10), miner()).unwrap();
+        let stale = local.blocks[2].hash.unwrap();
+        peer.add_block(issue(6..=10), wallet(1).address()).unwrap();
+        peer.add_block(issue(11..=15), wallet(1).address()).unwrap();
+
+        let json = serde_json::to_string(&peer).unwrap();
+        let restored: Blockchain = serde_json::from_str(&json).unwrap();
+        let tip = peer.blocks[3].hash.unwrap();
+        assert_eq!(restored.get_block_by_hash(&tip).unwrap().id, 3);
+
+        local.try_replace_chain(peer.blocks.clone()).unwrap();
+        assert_eq!(local.get_block_by_hash(&tip).unwrap().id, 3);
+        assert!(local.get_block_by_hash(&stale).is_none());
+    }
+
+    #[test]
+    fn test_find_transaction() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+
+        let (block_id, tx) = blockchain.find_transaction(7).unwrap();
+        assert_eq!(block_id, 2);
+        assert_eq!(tx.id, 7);
+        assert!(!tx.is_coinbase());
+        assert!(blockchain.find_transaction(11).is_none());
+    }
+
+    #[test]
+    fn test_transaction_history() {
+        let (alice, bob, carol) = (wallet(1), wallet(2), wallet(3));
+        let mut blockchain = two_per_block();
+        blockchain
+            .add_block(
+                vec![
+                    transfer(1, &issuer(), &alice.address(), 100),
+                    transfer_with_nonce(2, 1, &issuer(), &carol.address(), 100),
+                ],
+                miner(),
+            )
+            .unwrap();
+        blockchain
+            .add_block(
+                vec![
+                    transfer(3, &alice, &bob.address(), 10),
+                    transfer(4, &carol, &alice.address(), 5),
+                ],
+                miner(),
+            )
+            .unwrap();
+
+        let history: Vec<u64> = blockchain
+            .transaction_history(&alice.address())
+            .iter()
+            .map(|tx| tx.id)
+            .collect();
+        assert_eq!(history, vec![1, 3, 4]);
+        assert_eq!(blockchain.transaction_history(&bob.address()).len(), 1);
+    }
+
+    #[test]
+    fn test_transactions_for_is_lazy() {
+        let alice = address("Alice");
+        let mut blockchain = chain();
+        for block in 0..4 {
+            let transactions = (0..5)
+                .map(|offset| {
+                    let id = block * 5 + offset + 1;
+                    let receiver = if offset % 2 == 0 {
+                        alice
+                    } else {
+                        address("Bob")
+                    };
+                    transfer_with_nonce(id, id - 1, &issuer(), &receiver, 10)
+                })
+                .collect();
+            blockchain.add_block(transactions, miner()).unwrap();
+        }
+
+        let mut activity = blockchain.transactions_for(&alice);
+        let recent: Vec<(u64, u64)> = activity
+            .by_ref()
+            .take(3)
+            .map(|(block, tx)| (block.id, tx.id))
+            .collect();
+        assert_eq!(recent, vec![(1, 1), (1, 3), (1, 5)]);
+
+        // Nothing past the third match has been consumed
+        assert_eq!(
+            activity.next().map(|(block, tx)| (block.id, tx.id)),
+            Some((2, 6))
+        );
+        assert_eq!(activity.count(), 12 - 4);
+    }
+
+    #[test]
+    fn test_duplicate_transaction_id_across_blocks_is_rejected() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+
+        assert_eq!(
+            blockchain.add_block(issue(5..=9), miner()),
+            Err(BlockchainError::DuplicateTransaction { id: 5 })
+        );
+        assert_eq!(blockchain.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_duplicate_transaction_id_within_block_is_rejected() {
+        let mut blockchain = chain();
+
+        assert_eq!(
+            blockchain.add_block(issue([1, 2, 3, 2, 4]), miner()),
+            Err(BlockchainError::DuplicateTransaction { id: 2 })
+        );
+    }
+
+    #[test]
+    fn test_mempool_rejects_duplicate_transaction_id() {
+        let mut blockchain = chain();
+        blockchain.submit_transaction(issue([1]).remove(0)).unwrap();
+
+        assert_eq!(
+            blockchain.submit_transaction(issue([1]).remove(0)),
+            Err(BlockchainError::DuplicateTransaction { id: 1 })
+        );
+        assert_eq!(blockchain.pending_count(), 1);
+    }
+
+    #[test]
+    fn test_backwards_timestamp_is_invalid() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+
+        let parent_timestamp = blockchain.blocks[1].timestamp;
+        let tip = &mut blockchain.blocks[2];
+        tip.timestamp = parent_timestamp - 1;
+        tip.mine(tip.difficulty);
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::TimestampRegression { block_id: 2 })
+        );
+    }
+
+    #[test]
+    fn test_future_timestamp_is_invalid() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        let drift = blockchain.config.max_future_drift_secs;
+
+        let tip = &mut blockchain.blocks[1];
+        tip.timestamp = SystemClock.now_secs() + drift / 2;
+        tip.mine(tip.difficulty);
+        assert!(blockchain.validate_chain());
+
+        let tip = &mut blockchain.blocks[1];
+        tip.timestamp = SystemClock.now_secs() + drift + 60;
+        tip.mine(tip.difficulty);
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::FutureTimestamp { block_id: 1 })
+        );
+    }
+
+    #[test]
+    fn test_tampered_block_reports_hash_mismatch() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+        assert_eq!(blockchain.validate_chain_detailed(), Ok(()));
+
+        blockchain.blocks[1].transactions[1].outputs[0].amount = 1_000;
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::HashMismatch { block_id: 1 })
+        );
+    }
+
+    #[test]
+    fn test_unhashed_block_reports_missing_hash() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+
+        blockchain.blocks[1].hash = None;
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::MissingHash { block_id: 1 })
+        );
+    }
+
+    #[test]
+    fn test_relinked_block_reports_broken_link() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+
+        let tip = &mut blockchain.blocks[2];
+        tip.previous_hash = Hash::ZERO;
+        tip.mine(tip.difficulty);
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::BrokenLink { block_id: 2 })
+        );
+    }
+
+    #[test]
+    fn test_invalid_genesis_is_reported() {
+        let mut blockchain = chain();
+        blockchain.blocks[0].id = 1;
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::InvalidGenesis)
+        );
+
+        blockchain.blocks.clear();
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::EmptyChain)
+        );
+    }
+
+    #[test]
+    fn test_genesis_has_to_be_well_formed() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        let genesis = blockchain.blocks[0].clone();
+
+        // Resealed, so only the link to nothing gives it away
+        blockchain.blocks[0].previous_hash = Hash::from_bytes([1; 32]);
+        blockchain.blocks[0].seal();
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::InvalidGenesis)
+        );
+        assert_eq!(
+            blockchain.validate_headers(&blockchain.headers()),
+            Err(ValidationError::InvalidGenesis)
+        );
+
+        // With the genesis pinned, a resealed one is not accepted either
+        let mut pinned = Blockchain::with_genesis(
+            BlockchainConfig {
+                genesis_hash: genesis.hash,
+                ..config()
+            },
+            genesis,
+        )
+        .unwrap();
+        assert_eq!(pinned.validate_chain_detailed(), Ok(()));
+        pinned.blocks[0].timestamp += 1;
+        pinned.blocks[0].seal();
+        assert_eq!(
+            pinned.validate_chain_detailed(),
+            Err(ValidationError::InvalidGenesis)
+        );
+    }
+
+    #[test]
+    fn test_unmined_block_reports_insufficient_work() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+
+        let tip = &mut blockchain.blocks[1];
+        tip.difficulty = 64;
+        tip.hash = Some(tip.calculate_hash());
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::InsufficientWork { block_id: 1 })
+        );
+    }
+
+    #[test]
+    fn test_renumbered_block_reports_unexpected_id() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+
+        let tip = &mut blockchain.blocks[1];
+        tip.id = 7;
+        tip.mine(tip.difficulty);
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::UnexpectedId {
+                block_id: 7,
+                expected: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_pop_block_reverts_the_tip() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+        let tip_hash = blockchain.blocks[2].hash.unwrap();
+
+        let popped = blockchain.pop_block().unwrap();
+        assert_eq!(popped.id, 2);
+        assert_eq!(blockchain.blocks.last().unwrap().id, 1);
+        assert!(blockchain.validate_chain());
+
+        assert!(blockchain.get_block_by_hash(&tip_hash).is_none());
+        assert!(blockchain.find_transaction(6).is_none());
+        assert_eq!(blockchain.balance_of(&address("Receiver6")), Ok(0));
+        assert_eq!(blockchain.total_supply(), Ok(50));
+
+        // Transaction ids from the popped block can be reused
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_pop_block_keeps_genesis() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+
+        assert!(blockchain.pop_block().is_some());
+        assert!(blockchain.pop_block().is_none());
+        assert_eq!(blockchain.blocks.len(), 1);
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_fees_go_to_the_miner() {
+        let (alice, bob) = (wallet(1), wallet(2));
+        let mut blockchain = two_per_block();
+        blockchain
+            .add_block(
+                vec![
+                    transfer(1, &issuer(), &alice.address(), 100),
+                    transfer_with_nonce(2, 1, &issuer(), &bob.address(), 100),
+                ],
+                miner(),
+            )
+            .unwrap();
+
+        blockchain
+            .add_block(
+                vec![
+                    transfer_with_fee(3, &alice, &bob.address(), 40, 3),
+                    transfer_with_fee(4, &bob, &alice.address(), 10, 2),
+                ],
+                miner(),
+            )
+            .unwrap();
+
+        let block = &blockchain.blocks[2];
+        assert_eq!(block.total_fees(), 5);
+        assert_eq!(block.transactions[0].outputs[0].amount, 55);
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(67));
+        assert_eq!(blockchain.balance_of(&bob.address()), Ok(128));
+        assert_eq!(blockchain.balance_of(&miner()), Ok(105));
+        assert_eq!(blockchain.total_supply(), Ok(100));
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_fee_counts_towards_required_funds() {
+        let (alice, bob) = (wallet(1), wallet(2));
+        let mut blockchain = chain();
+        blockchain
+            .submit_transaction(transfer(1, &issuer(), &alice.address(), 10))
+            .unwrap();
+
+        assert_eq!(
+            blockchain.submit_transaction(transfer_with_fee(2, &alice, &bob.address(), 10, 1)),
+            Err(BlockchainError::InsufficientFunds {
+                account: alice.address(),
+                available: 10,
+                required: 11,
+            })
+        );
+    }
+
+    #[test]
+    fn test_block_assembly_orders_by_fee() {
+        let mut blockchain = two_per_block();
+        let senders: Vec<Wallet> = (1..=4).map(wallet).collect();
+        for (id, sender) in (1..).zip(&senders) {
+            let tx = transfer_with_nonce(id, id - 1, &issuer(), &sender.address(), 20);
+            blockchain.submit_transaction(tx).unwrap();
+        }
+        blockchain.flush_pending(miner()).unwrap();
+        blockchain.flush_pending(miner()).unwrap();
+
+        for ((id, fee), sender) in [(5, 0), (6, 4), (7, 1), (8, 4)].into_iter().zip(&senders) {
+            let tx = transfer_with_fee(id, sender, &address("Receiver"), 10, fee);
+            blockchain.submit_transaction(tx).unwrap();
+        }
+        blockchain.flush_pending(miner()).unwrap();
+        blockchain.flush_pending(miner()).unwrap();
+        let ids: Vec<Vec<u64>> = blockchain.blocks[3..]
+            .iter()
+            .map(|block| block.transactions[1..].iter().map(|tx| tx.id).collect())
+            .collect();
+        assert_eq!(ids, vec![vec![6, 8], vec![7, 5]]);
+        assert_eq!(blockchain.blocks[3].transactions[0].outputs[0].amount, 58);
+    }
+
+    #[test]
+    fn test_block_assembly_takes_funding_before_fees() {
+        let (alice, bob) = (wallet(1), wallet(2));
+        let mut blockchain = two_per_block();
+        blockchain
+            .submit_transaction(transfer(1, &issuer(), &alice.address(), 100))
+            .unwrap();
+        blockchain
+            .submit_transaction(transfer_with_fee(2, &alice, &bob.address(), 50, 5))
+            .unwrap();
+
+        assert_eq!(blockchain.flush_pending(miner()), Ok(Some(1)));
+        let ids: Vec<u64> = blockchain.blocks[1].transactions[1..]
+            .iter()
+            .map(|tx| tx.id)
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(blockchain.balance_of(&bob.address()), Ok(50));
+        assert_eq!(blockchain.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_display_prints_a_line_per_block() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+
+        let output = blockchain.to_string();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], blockchain.blocks[0].to_string());
+        assert!(lines[1].starts_with("Block #1 |"));
+        assert!(lines[1].contains("| 6 txs |"));
+    }
+
+    #[test]
+    fn test_zero_quantity_transaction_is_rejected() {
+        let mut blockchain = two_per_block();
+        let result = blockchain.add_block(
+            vec![
+                transfer(1, &issuer(), &address("Alice"), 10),
+                transfer_with_nonce(2, 1, &issuer(), &address("Bob"), 0),
+            ],
+            miner(),
+        );
+
+        assert_eq!(
+            result,
+            Err(BlockchainError::InvalidTransaction {
+                id: 2,
+                reason: "quantity must be greater than zero".to_string(),
+            })
+        );
+        assert_eq!(blockchain.blocks.len(), 1);
+    }
+
+    #[test]
+    fn test_self_transfer_is_rejected() {
+        let alice = wallet(1);
+        let mut blockchain = chain();
+        blockchain
+            .submit_transaction(transfer(1, &issuer(), &alice.address(), 10))
+            .unwrap();
+
+        assert!(matches!(
+            blockchain.submit_transaction(transfer(2, &alice, &alice.address(), 5)),
+            Err(BlockchainError::InvalidTransaction { id: 2, .. })
+        ));
+        assert_eq!(
+            blockchain.submit_transaction(transfer(3, &alice, &address("Bob"), 5)),
+            Ok(None)
+        );
+        assert_eq!(blockchain.pending_count(), 2);
+    }
+
+    #[test]
+    fn test_balance_overflow_is_an_error() {
+        let alice = wallet(1);
+        let mut blockchain = two_per_block();
+        blockchain
+            .add_block(
+                vec![
+                    transfer(1, &issuer(), &alice.address(), u64::MAX - 10),
+                    transfer_with_nonce(2, 1, &issuer(), &address("Bob"), 10),
+                ],
+                miner(),
+            )
+            .unwrap();
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(u64::MAX - 10));
+
+        let result = blockchain.add_block(
+            vec![
+                transfer_with_nonce(3, 2, &issuer(), &alice.address(), 5),
+                transfer_with_nonce(4, 3, &issuer(), &alice.address(), 6),
+            ],
+            miner(),
+        );
+        assert_eq!(result, Err(BlockchainError::Overflow));
+        assert_eq!(blockchain.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_fee_overflow_is_an_error() {
+        let alice = wallet(1);
+        let mut blockchain = chain();
+        blockchain
+            .submit_transaction(transfer(1, &issuer(), &alice.address(), 100))
+            .unwrap();
+
+        assert_eq!(
+            blockchain.submit_transaction(transfer_with_fee(
+                2,
+                &alice,
+                &address("Bob"),
+                u64::MAX,
+                1
+            )),
+            Err(BlockchainError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_supply_overflow_is_an_error() {
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            block_reward: u64::MAX / 2 + 1,
+            ..config()
+        });
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        assert_eq!(blockchain.total_supply(), Ok(u64::MAX / 2 + 1));
+
+        blockchain
+            .add_block(issue(6..=10), address("Other"))
+            .unwrap();
+        assert_eq!(blockchain.total_supply(), Err(BlockchainError::Overflow));
+    }
+
+    /// Adds the bytes up column by column. Useless for security, but cheap and
+    /// clearly not SHA256.
+    struct SumHasher;
+
+    impl BlockHasher for SumHasher {
+        fn hash(&self, data: &[u8]) -> [u8; 32] {
+            let mut out = [0u8; 32];
+            for (index, byte) in data.iter().enumerate() {
+                out[index % 32] = out[index % 32].wrapping_add(*byte);
+            }
+            out
+        }
+    }
+
+    #[test]
+    fn test_custom_hasher_seals_and_validates_blocks() {
+        let mut blockchain = Blockchain::with_hasher(config(), SumHasher);
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        assert!(blockchain.validate_chain());
+
+        let tip = &blockchain.blocks[1];
+        assert_eq!(tip.hash, Some(tip.calculate_hash_with(&SumHasher)));
+        assert_ne!(tip.hash, Some(tip.calculate_hash()));
+        assert!(blockchain.get_block_by_hash(&tip.hash.unwrap()).is_some());
+
+        // The same blocks do not check out under SHA256
+        assert_eq!(
+            fork(&blockchain).validate_chain_detailed(),
+            Err(ValidationError::HashMismatch { block_id: 0 })
+        );
+    }
+
+    #[test]
+    fn test_height_and_len() {
+        let mut blockchain = chain();
+        assert_eq!(blockchain.height(), 0);
+        assert_eq!(blockchain.len(), 1);
+        assert!(!blockchain.is_empty());
+        assert_eq!(blockchain.latest_block().unwrap().id, 0);
+
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+        assert_eq!(blockchain.height(), 2);
+        assert_eq!(blockchain.len(), 3);
+        assert_eq!(
+            blockchain.latest_block().unwrap().hash,
+            blockchain.blocks[2].hash
+        );
+
+        blockchain.pop_block();
+        assert_eq!(blockchain.height(), 1);
+        assert_eq!(blockchain.len(), 2);
+    }
+
+    fn two_to_four_per_block() -> Blockchain {
+        Blockchain::new(BlockchainConfig {
+            min_transactions_per_block: 2,
+            max_transactions_per_block: 4,
+            ..config()
+        })
+    }
+
+    #[test]
+    fn test_block_of_minimum_size_is_sealed() {
+        let mut blockchain = two_to_four_per_block();
+        let id = blockchain.add_block(issue(1..=2), miner()).unwrap();
+
+        let block = blockchain.get_block_by_id(id).unwrap();
+        assert_eq!(block.transactions.len(), 3);
+        assert_eq!(block.hash, Some(block.calculate_hash()));
+
+        blockchain.add_block(issue(3..=6), miner()).unwrap();
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_block_outside_the_bounds_is_rejected() {
+        let mut blockchain = two_to_four_per_block();
+        let expected = |got| {
+            Err(BlockchainError::WrongTransactionCount {
+                min: 2,
+                max: 4,
+                got,
+            })
+        };
+
+        assert_eq!(blockchain.add_block(issue([1]), miner()), expected(1));
+        assert_eq!(blockchain.add_block(issue(1..=5), miner()), expected(5));
+        assert_eq!(blockchain.len(), 1);
+    }
+
+    #[test]
+    fn test_flush_pending_waits_for_the_minimum() {
+        let mut blockchain = two_to_four_per_block();
+        blockchain.submit_transaction(issue([1]).remove(0)).unwrap();
+        assert_eq!(blockchain.flush_pending(miner()), Ok(None));
+
+        blockchain.submit_transaction(issue([2]).remove(0)).unwrap();
+        assert_eq!(blockchain.flush_pending(miner()), Ok(Some(1)));
+    }
+
+    #[test]
+    fn test_oversized_block_is_invalid() {
+        let mut blockchain = two_to_four_per_block();
+        blockchain.add_block(issue(1..=4), miner()).unwrap();
+
+        let tip = &mut blockchain.blocks[1];
+        tip.transactions.push(issue([5]).remove(0));
+        tip.mine(tip.difficulty);
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::WrongTransactionCount {
+                block_id: 1,
+                count: 5
+            })
+        );
+    }
+
+    fn spend(
+        id: u64,
+        nonce: u64,
+        from: &Wallet,
+        inputs: Vec<TxInput>,
+        quantity: u64,
+    ) -> Transaction {
+        let mut tx = transfer_with_nonce(id, nonce, from, &address("Shop"), quantity);
+        tx.inputs = inputs;
+        from.sign(&mut tx);
+        tx
+    }
+
+    #[test]
+    fn test_block_spending_unknown_output_is_rejected() {
+        let alice = wallet(1);
+        let mut blockchain = two_per_block();
+        let funding = transfer(1, &issuer(), &alice.address(), 100);
+        let source = TxInput {
+            source_tx: funding.hash(),
+            output_index: 0,
+        };
+
+        let result = blockchain.add_block(vec![spend(2, 0, &alice, vec![source], 10)], miner());
+        assert_eq!(result, Err(BlockchainError::MissingOutput { id: 2 }));
+
+        blockchain
+            .add_block(
+                vec![funding, spend(2, 0, &alice, vec![source], 10)],
+                miner(),
+            )
+            .unwrap();
+        assert!(blockchain.utxo_set().get(&source).is_none());
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(90));
+        assert_eq!(blockchain.balance_of(&address("Shop")), Ok(10));
+    }
+
+    #[test]
+    fn test_block_spending_spent_output_is_rejected() {
+        let alice = wallet(1);
+        let mut blockchain = two_per_block();
+        let funding = transfer(1, &issuer(), &alice.address(), 100);
+        let source = TxInput {
+            source_tx: funding.hash(),
+            output_index: 0,
+        };
+        blockchain
+            .add_block(
+                vec![funding, spend(2, 0, &alice, vec![source], 10)],
+                miner(),
+            )
+            .unwrap();
+
+        let result = blockchain.add_block(vec![spend(3, 1, &alice, vec![source], 10)], miner());
+        assert_eq!(result, Err(BlockchainError::MissingOutput { id: 3 }));
+        assert_eq!(blockchain.len(), 2);
+    }
+
+    #[test]
+    fn test_pop_block_restores_spent_outputs() {
+        let alice = wallet(1);
+        let mut blockchain = two_per_block();
+        let funding = transfer(1, &issuer(), &alice.address(), 100);
+        let source = TxInput {
+            source_tx: funding.hash(),
+            output_index: 0,
+        };
+        blockchain.add_block(vec![funding], miner()).unwrap();
+        blockchain
+            .add_block(vec![spend(2, 0, &alice, vec![source], 10)], miner())
+            .unwrap();
+        assert!(blockchain.utxo_set().get(&source).is_none());
+
+        blockchain.pop_block();
+        assert!(blockchain.utxo_set().get(&source).is_some());
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(100));
+    }
+
+    #[test]
+    fn test_custom_genesis_with_allocations() {
+        let alice = wallet(1);
+        let mut genesis = Block::with_capacity(0, Hash::ZERO, 2);
+        genesis.add_transaction(Transaction::coinbase(1, alice.address(), 500));
+        genesis.add_transaction(Transaction::coinbase(2, address("Bob"), 300));
+
+        let mut spending = genesis.clone();
+        spending
+            .transactions
+            .push(transfer(3, &alice, &address("Carol"), 1_000));
+        spending.hash = None;
+        assert!(matches!(
+            Blockchain::with_genesis(config(), spending),
+            Err(BlockchainError::InsufficientFunds { .. })
+        ));
+
+        let mut blockchain = Blockchain::with_genesis(config(), genesis.clone()).unwrap();
+        assert!(blockchain.validate_chain());
+        assert_eq!(blockchain.blocks[0].hash, genesis.hash);
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(500));
+        assert_eq!(blockchain.total_supply(), Ok(800));
+
+        blockchain
+            .add_block(vec![transfer(1, &alice, &address("Carol"), 200)], miner())
+            .unwrap();
+        assert_eq!(blockchain.blocks[1].previous_hash, genesis.hash.unwrap());
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(300));
+        assert!(blockchain.validate_chain());
+
+        blockchain.blocks[0].transactions[1].outputs[0].amount = 3_000;
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::HashMismatch { block_id: 0 })
+        );
+    }
+
+    #[test]
+    fn test_unsealed_genesis_is_sealed() {
+        let genesis = Block::new(0, Hash::ZERO);
+        let blockchain = Blockchain::with_genesis(config(), genesis.clone()).unwrap();
+
+        assert_eq!(blockchain.blocks[0].hash, Some(genesis.calculate_hash()));
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_replayed_nonce_is_rejected() {
+        let alice = wallet(1);
+        let mut blockchain = chain();
+        blockchain
+            .add_block(vec![transfer(1, &issuer(), &alice.address(), 100)], miner())
+            .unwrap();
+        blockchain
+            .add_block(vec![transfer(2, &alice, &address("Bob"), 10)], miner())
+            .unwrap();
+
+        let replay = transfer(3, &alice, &address("Bob"), 10);
+        assert_eq!(
+            blockchain.add_block(vec![replay], miner()),
+            Err(BlockchainError::BadNonce {
+                id: 3,
+                expected: 1,
+                got: 0
+            })
+        );
+        assert_eq!(blockchain.balance_of(&address("Bob")), Ok(10));
+    }
+
+    #[test]
+    fn test_skipped_nonce_is_rejected() {
+        let mut blockchain = chain();
+        let skipped = transfer_with_nonce(1, 1, &issuer(), &address("Alice"), 10);
+        assert_eq!(
+            blockchain.submit_transaction(skipped),
+            Err(BlockchainError::BadNonce {
+                id: 1,
+                expected: 0,
+                got: 1
+            })
+        );
+        assert_eq!(blockchain.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_next_nonce_counts_pending_transactions() {
+        let mut blockchain = chain();
+        let issuer = issuer().address();
+        assert_eq!(blockchain.next_nonce(&issuer), 0);
+
+        for tx in issue(1..=3) {
+            blockchain.submit_transaction(tx).unwrap();
+        }
+        assert_eq!(blockchain.next_nonce(&issuer), 3);
+
+        blockchain.flush_pending(miner()).unwrap();
+        assert_eq!(blockchain.nonces[&issuer], 3);
+        assert_eq!(blockchain.next_nonce(&issuer), 3);
+        assert_eq!(blockchain.next_nonce(&address("Nobody")), 0);
+    }
+
+    #[test]
+    fn test_block_time_statistics() {
+        let mut blockchain = chain();
+        assert_eq!(blockchain.average_block_time(), None);
+
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+        blockchain.add_block(issue(11..=15), miner()).unwrap();
+        for (block, timestamp) in blockchain.blocks.iter_mut().zip([100, 110, 130, 160]) {
+            block.timestamp = timestamp;
+        }
+
+        assert_eq!(blockchain.average_block_time(), Some(20.0));
+        assert_eq!(blockchain.block_time_between(1, 3), Some(50));
+        assert_eq!(blockchain.block_time_between(3, 1), Some(50));
+        assert_eq!(blockchain.block_time_between(0, 4), None);
+    }
+
+    #[test]
+    fn test_submitted_transactions_are_numbered() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        assert_eq!(blockchain.next_transaction_id(), 6);
+
+        let issuer = issuer();
+        for (nonce, receiver) in (5..).zip(["Alice", "Bob", "Carol"]) {
+            let mut tx = Transaction::simple(issuer.address(), address(receiver), 10);
+            tx.nonce = nonce;
+            issuer.sign(&mut tx);
+            blockchain.submit_transaction(tx).unwrap();
+        }
+
+        let ids: Vec<u64> = blockchain
+            .pending_transactions()
+            .iter()
+            .map(|tx| tx.id)
+            .collect();
+        assert_eq!(ids, vec![6, 7, 8]);
+        assert_eq!(blockchain.next_transaction_id(), 9);
+        blockchain.flush_pending(miner()).unwrap();
+        assert!(blockchain.validate_chain());
+        assert_eq!(blockchain.next_transaction_id(), 9);
+
+        blockchain.pop_block();
+        assert_eq!(blockchain.next_transaction_id(), 6);
+    }
+
+    #[test]
+    fn test_pruned_chain_still_validates() {
+        let mut blockchain = chain();
+        for first in (1..=20).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        let hashes: Vec<Option<Hash>> = blockchain.iter().map(|block| block.hash).collect();
+
+        blockchain.prune(2);
+        assert!(blockchain.blocks[..3]
+            .iter()
+            .all(|block| block.is_pruned() && block.transactions.is_empty()));
+        assert!(blockchain.blocks[3..]
+            .iter()
+            .all(|block| !block.is_pruned()));
+        assert_eq!(
+            blockchain
+                .iter()
+                .map(|block| block.hash)
+                .collect::<Vec<_>>(),
+            hashes
+        );
+        assert!(blockchain.validate_chain());
+
+        blockchain.blocks[1].previous_hash = Hash::ZERO;
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::HashMismatch { block_id: 1 })
+        );
+    }
+
+    #[test]
+    fn test_pruned_state_survives_a_reload() {
+        let alice = wallet(1);
+        let mut blockchain = chain();
+        blockchain
+            .add_block(vec![transfer(1, &issuer(), &alice.address(), 100)], miner())
+            .unwrap();
+        blockchain
+            .add_block(vec![transfer(2, &alice, &address("Bob"), 30)], miner())
+            .unwrap();
+        blockchain.prune(0);
+
+        let json = serde_json::to_string(&blockchain).unwrap();
+        let mut loaded: Blockchain = serde_json::from_str(&json).unwrap();
+        assert!(loaded.iter().all(Block::is_pruned));
+        assert_eq!(loaded.balance_of(&alice.address()), Ok(70));
+        assert_eq!(loaded.next_nonce(&alice.address()), 1);
+        assert_eq!(loaded.next_transaction_id(), 3);
+
+        // Alice's change sits in a pruned block
+        loaded
+            .add_block(
+                vec![transfer_with_nonce(3, 1, &alice, &address("Bob"), 50)],
+                miner(),
+            )
+            .unwrap();
+        assert_eq!(loaded.balance_of(&address("Bob")), Ok(80));
+        assert!(loaded.validate_chain());
+
+        // The pruned blocks stay put
+        assert!(loaded.pop_block().is_some());
+        assert!(loaded.pop_block().is_none());
+        assert_eq!(
+            loaded.rebuild_from(2, vec![issue([4])]),
+            Err(BlockchainError::PrunedHistory { height: 3 })
+        );
+    }
+
+    #[test]
+    fn test_pruned_root_is_only_trusted_below_the_prune_height() {
+        let mut blockchain = chain();
+        for first in (1..=15).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        let mut peer = fork(&blockchain);
+        blockchain.prune(2);
+
+        // The prune height is saved along with the blocks
+        let json = serde_json::to_string(&blockchain).unwrap();
+        let loaded: Blockchain = serde_json::from_str(&json).unwrap();
+        assert!(loaded.validate_chain());
+
+        // A pruned root does not vouch for transactions left next to it
+        let kept = blockchain.blocks[3].clone();
+        let mut edited = kept.clone();
+        edited.pruned_root = Some(edited.merkle_root());
+        edited.transactions[0].outputs[0].amount = 1_000_000;
+        blockchain.blocks[3] = edited;
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::PrunedWithTransactions { block_id: 3 })
+        );
+
+        // Nor does it stand in for transactions above the prune height
+        blockchain.blocks[3] = kept;
+        blockchain.blocks[3].prune();
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::UnexpectedPrune { block_id: 3 })
+        );
+        blockchain.blocks[3] = peer.blocks[3].clone();
+
+        // Blocks from elsewhere always have to bring their transactions
+        peer.add_block(issue(16..=20), miner()).unwrap();
+        peer.add_block(issue(21..=25), miner()).unwrap();
+        let mut pruned = peer.blocks[4].clone();
+        pruned.prune();
+        assert_eq!(
+            blockchain.accept_block(pruned),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::UnexpectedPrune { block_id: 4 }
+            ))
+        );
+        let mut candidate = peer.blocks.clone();
+        candidate[5].prune();
+        assert_eq!(
+            blockchain.try_replace_chain(candidate),
+            Err(BlockchainError::InvalidChain(
+                ValidationError::UnexpectedPrune { block_id: 5 }
+            ))
+        );
+        assert_eq!(blockchain.try_replace_chain(peer.blocks.clone()), Ok(true));
+        assert!(blockchain.blocks[1].is_pruned());
+        assert!(blockchain.validate_chain());
+    }
+
+    fn checkpointed_chain() -> Blockchain {
+        let mut blockchain = chain();
+        for first in (1..=15).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        blockchain
+    }
+
+    #[test]
+    fn test_matching_checkpoint_skips_the_blocks_below_it() {
+        let mut blockchain = checkpointed_chain();
+        let hash = blockchain.blocks[2].hash.unwrap();
+        blockchain.set_checkpoint(2, hash);
+
+        // Not looked at any more, so a change below the checkpoint goes unseen
+        blockchain.blocks[1].transactions[1].outputs[0].amount += 1;
+        assert_eq!(blockchain.validate_chain_detailed(), Ok(()));
+
+        blockchain.blocks[3].transactions[1].outputs[0].amount += 1;
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::HashMismatch { block_id: 3 })
+        );
+    }
+
+    #[test]
+    fn test_mismatched_checkpoint_is_rejected() {
+        let mut blockchain = checkpointed_chain();
+        blockchain.set_checkpoint(2, Hash::ZERO);
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::CheckpointMismatch { block_id: 2 })
+        );
+
+        // Beyond the tip, the checkpoint does not apply yet
+        blockchain.set_checkpoint(10, Hash::ZERO);
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_validate_stream() {
+        let mut blockchain = chain();
+        for first in (1..=15).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        let blocks = blockchain.blocks.clone();
+        assert_eq!(
+            blockchain.validate_stream(blocks.clone().into_iter()),
+            Ok(())
+        );
+        assert_eq!(
+            blockchain.validate_stream(std::iter::empty()),
+            Err(ValidationError::EmptyChain)
+        );
+
+        let mut relinked = blocks.clone();
+        relinked[2].previous_hash = Hash::ZERO;
+        relinked[2].seal();
+        assert_eq!(
+            blockchain.validate_stream(relinked.into_iter()),
+            Err(ValidationError::BrokenLink { block_id: 2 })
+        );
+        assert_eq!(
+            blockchain.validate_stream(blocks.into_iter().skip(1)),
+            Err(ValidationError::InvalidGenesis)
+        );
+    }
+
+    #[test]
+    fn test_validate_stream_follows_retargets() {
+        let mut blockchain = retargeting_chain();
+        for first in (1..=20).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        let blocks = blockchain.blocks.clone();
+        assert_eq!(blockchain.validate_stream(blocks.into_iter()), Ok(()));
+    }
+
+    #[test]
+    fn test_mock_clock_makes_hashes_reproducible() {
+        let build = || {
+            let clock = MockClock::new(1_700_000_000);
+            let mut blockchain = Blockchain::with_clock(config(), clock.clone());
+            for first in (1..=10).step_by(5) {
+                clock.advance(10);
+                blockchain
+                    .add_block(issue(first..first + 5), miner())
+                    .unwrap();
+            }
+            blockchain
+        };
+
+        let (first, second) = (build(), build());
+        assert!(first.validate_chain());
+        assert_eq!(first.blocks[2].timestamp, 1_700_000_020);
+        let hashes = |blockchain: &Blockchain| -> Vec<Option<Hash>> {
+            blockchain.iter().map(|block| block.hash).collect()
+        };
+        assert_eq!(hashes(&first), hashes(&second));
+    }
+
+    #[test]
+    fn test_common_ancestor_and_divergence_point() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        local.add_block(issue(6..=10), miner()).unwrap();
+        let mut peer = fork(&local);
+        assert_eq!(local.common_ancestor(&peer), Some(2));
+        assert_eq!(local.divergence_point(&peer), None);
+
+        local.add_block(issue(11..=15), miner()).unwrap();
+        assert_eq!(local.divergence_point(&peer), Some(3));
+        peer.add_block(issue(11..=15), wallet(1).address()).unwrap();
+        peer.add_block(issue(16..=20), wallet(1).address()).unwrap();
+
+        assert_eq!(local.common_ancestor(&peer), Some(2));
+        assert_eq!(peer.common_ancestor(&local), Some(2));
+        assert_eq!(local.divergence_point(&peer), Some(3));
+
+        let stranger =
+            Blockchain::with_genesis(config(), Block::new(0, Hash::from_bytes([1; 32]))).unwrap();
+        assert_eq!(local.common_ancestor(&stranger), None);
+        assert_eq!(local.divergence_point(&stranger), Some(0));
+    }
+
+    #[test]
+    fn test_accept_block_from_a_peer() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let mut peer = fork(&local);
+        peer.add_block(issue(6..=10), miner()).unwrap();
+
+        let block = peer.blocks[2].clone();
+        local.accept_block(block.clone()).unwrap();
+        assert_eq!(local.latest_block().unwrap().hash, block.hash);
+        assert_eq!(local.find_transaction(8).unwrap().0, 2);
+        assert!(local.validate_chain());
+
+        assert_eq!(
+            local.accept_block(block),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::UnexpectedId {
+                    block_id: 2,
+                    expected: 3
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_accept_block_rejects_a_wrong_previous_hash() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let mut peer = fork(&local);
+        peer.add_block(issue(6..=10), miner()).unwrap();
+
+        let mut block = peer.blocks[2].clone();
+        block.previous_hash = local.blocks[0].hash.unwrap();
+        block.seal();
+        assert_eq!(
+            local.accept_block(block),
+            Err(BlockchainError::InvalidBlock(ValidationError::BrokenLink {
+                block_id: 2
+            }))
+        );
+
+        let mut overpaid = peer.blocks[2].clone();
+        overpaid.transactions[0].outputs[0].amount += 1;
+        overpaid.seal();
+        assert!(matches!(
+            local.accept_block(overpaid),
+            Err(BlockchainError::InvalidTransaction { id: 2, .. })
+        ));
+        assert_eq!(local.len(), 2);
+    }
+
+    #[test]
+    fn test_memo_size_is_limited() {
+        let issuer = issuer();
+        let mut blockchain = chain();
+        let with_memo = |id: u64, memo: Vec<u8>| {
+            let mut tx = transfer_with_nonce(id, id - 1, &issuer, &address("Alice"), 10);
+            tx.memo = Some(memo);
+            issuer.sign(&mut tx);
+            tx
+        };
+
+        blockchain
+            .add_block(vec![with_memo(1, b"rent for May".to_vec())], miner())
+            .unwrap();
+        assert_eq!(
+            blockchain.blocks[1].transactions[1].memo.as_deref(),
+            Some(&b"rent for May"[..])
+        );
+
+        assert_eq!(
+            blockchain.add_block(vec![with_memo(2, vec![0; 257])], miner()),
+            Err(BlockchainError::MemoTooLarge {
+                id: 2,
+                size: 257,
+                max: 256
+            })
+        );
+    }
+
+    #[test]
+    fn test_time_locked_transaction_waits_for_its_time() {
+        let clock = MockClock::new(1_700_000_000);
+        let mut blockchain = Blockchain::with_clock(config(), clock.clone());
+        let issuer = issuer();
+        let mut locked = transfer(1, &issuer, &address("Alice"), 10);
+        locked.not_before = Some(1_700_000_100);
+        issuer.sign(&mut locked);
+
+        assert_eq!(
+            blockchain.add_block(vec![locked.clone()], miner()),
+            Err(BlockchainError::TimeLocked {
+                id: 1,
+                not_before: 1_700_000_100
+            })
+        );
+        assert_eq!(blockchain.len(), 1);
+
+        clock.advance(100);
+        blockchain.add_block(vec![locked], miner()).unwrap();
+        assert_eq!(blockchain.balance_of(&address("Alice")), Ok(10));
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_balance_at_an_earlier_height() {
+        let (alice, bob) = (wallet(1), wallet(2));
+        let mut blockchain = chain();
+        blockchain
+            .add_block(vec![transfer(1, &issuer(), &alice.address(), 100)], miner())
+            .unwrap();
+        blockchain
+            .add_block(vec![transfer(2, &alice, &bob.address(), 30)], miner())
+            .unwrap();
+        blockchain
+            .add_block(
+                vec![transfer_with_nonce(3, 1, &alice, &bob.address(), 20)],
+                miner(),
+            )
+            .unwrap();
+
+        assert_eq!(blockchain.balance_of_at(&alice.address(), 0), 0);
+        assert_eq!(blockchain.balance_of_at(&alice.address(), 1), 100);
+        assert_eq!(blockchain.balance_of_at(&alice.address(), 2), 70);
+        assert_eq!(blockchain.balance_of_at(&bob.address(), 2), 30);
+        assert_eq!(blockchain.balance_of_at(&miner(), 2), 100);
+        assert_eq!(
+            Ok(blockchain.balance_of_at(&alice.address(), 10)),
+            blockchain.balance_of(&alice.address())
+        );
+        assert_eq!(blockchain.balance_of_at(&address("Nobody"), 3), 0);
+    }
+
+    #[test]
+    fn test_block_added_callbacks() {
+        use std::sync::{Arc, Mutex};
+
+        let mut blockchain = chain();
+        let added = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&added);
+        blockchain.on_block_added(Box::new(move |block| seen.lock().unwrap().push(block.id)));
+
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        assert!(blockchain.add_block(issue(1..=5), miner()).is_err());
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+        assert_eq!(*added.lock().unwrap(), vec![1, 2]);
+
+        let mut peer = fork(&blockchain);
+        blockchain.pop_block();
+        peer.add_block(issue(11..=15), miner()).unwrap();
+        blockchain.try_replace_chain(peer.blocks.clone()).unwrap();
+        assert_eq!(*added.lock().unwrap(), vec![1, 2, 2, 3]);
+    }
+
+    #[test]
+    fn test_orphans_connect_once_the_gap_is_filled() {
+        let mut local = chain();
+        let mut peer = fork(&local);
+        for first in (1..=20).step_by(5) {
+            peer.add_block(issue(first..first + 5), miner()).unwrap();
+        }
+
+        let mut stray = peer.blocks[1].clone();
+        stray.previous_hash = Hash::ZERO;
+        for block in [&peer.blocks[3], &peer.blocks[4], &stray, &peer.blocks[2]] {
+            local.accept_block(block.clone()).unwrap();
+        }
+        assert_eq!(local.height(), 0);
+        assert_eq!(local.orphan_count(), 4);
+
+        assert_eq!(
+            local.accept_block(peer.blocks[1].clone()),
+            Ok(BlockStatus::OnChain)
+        );
+        assert_eq!(local.height(), 4);
+        assert_eq!(local.orphan_count(), 1);
+        assert_eq!(local.latest_block().unwrap().hash, peer.blocks[4].hash);
+        assert!(local.validate_chain());
+        assert_eq!(local.balance_of(&miner()), peer.balance_of(&miner()));
+    }
+
+    #[test]
+    fn test_orphan_pool_keeps_the_newest_blocks() {
+        let mut local = Blockchain::new(BlockchainConfig {
+            max_orphans: 2,
+            ..config()
+        });
+        let mut peer = fork(&local);
+        for first in (1..=20).step_by(5) {
+            peer.add_block(issue(first..first + 5), miner()).unwrap();
+        }
+
+        for id in 2..=4 {
+            assert_eq!(
+                local.accept_block(peer.blocks[id].clone()),
+                Ok(BlockStatus::Orphan)
+            );
+        }
+        assert_eq!(local.orphan_count(), 2);
+        // Seeing a held block again does not push anything out
+        local.accept_block(peer.blocks[4].clone()).unwrap();
+        assert_eq!(local.orphan_count(), 2);
+
+        // Block 2 was dropped, so block 1 connects on its own
+        assert_eq!(
+            local.accept_block(peer.blocks[1].clone()),
+            Ok(BlockStatus::OnChain)
+        );
+        assert_eq!(local.height(), 1);
+        assert_eq!(local.orphan_count(), 2);
+        assert_eq!(
+            local.accept_block(peer.blocks[2].clone()),
+            Ok(BlockStatus::OnChain)
+        );
+        assert_eq!(local.height(), 4);
+        assert_eq!(local.orphan_count(), 0);
+    }
+
+    #[test]
+    fn test_initial_allocations_fund_accounts_at_genesis() {
+        let alice = wallet(1);
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            initial_allocations: vec![(alice.address(), 1_000), (address("Bob"), 5)],
+            ..config()
+        });
+        assert_eq!(blockchain.blocks[0].transactions.len(), 2);
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(1_000));
+        assert_eq!(blockchain.balance_of_at(&alice.address(), 0), 1_000);
+        assert_eq!(blockchain.total_supply(), Ok(1_005));
+
+        blockchain
+            .add_block(vec![transfer(1, &alice, &address("Carol"), 300)], miner())
+            .unwrap();
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(700));
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_allocations_do_not_collide_with_coinbases() {
+        let alice = wallet(1);
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            initial_allocations: vec![(alice.address(), 50), (alice.address(), 50)],
+            ..config()
+        });
+        let genesis = &blockchain.blocks[0].transactions;
+        assert_ne!(genesis[0].hash(), genesis[1].hash());
+
+        blockchain.add_block(issue([1]), alice.address()).unwrap();
+        assert_ne!(
+            blockchain.blocks[1].transactions[0].hash(),
+            Transaction::allocation(1, alice.address(), 50).hash()
+        );
+        assert_eq!(blockchain.utxo_set().unspent(&alice.address()).len(), 3);
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(150));
+
+        blockchain
+            .add_block(vec![transfer(2, &alice, &address("Bob"), 120)], miner())
+            .unwrap();
+        assert_eq!(blockchain.balance_of(&address("Bob")), Ok(120));
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_coinbase_rewards_mature_before_they_can_be_spent() {
+        let alice = wallet(1);
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            coinbase_maturity: 2,
+            ..config()
+        });
+        blockchain.add_block(issue([1]), alice.address()).unwrap();
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(50));
+
+        let spend = transfer(10, &alice, &address("Bob"), 30);
+        assert_eq!(
+            blockchain.add_block(vec![spend.clone()], miner()),
+            Err(BlockchainError::ImmatureCoinbase { id: 10 })
+        );
+        assert_eq!(
+            blockchain.submit_transaction(spend.clone()),
+            Err(BlockchainError::ImmatureCoinbase { id: 10 })
+        );
+        blockchain.add_block(issue([2]), miner()).unwrap();
+
+        // Block 3 is two blocks after the reward
+        blockchain.add_block(vec![spend], miner()).unwrap();
+        assert_eq!(blockchain.balance_of(&address("Bob")), Ok(30));
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_blocks_from_another_network_are_rejected() {
+        let on = |network_id| {
+            Blockchain::new(BlockchainConfig {
+                network_id,
+                ..config()
+            })
+        };
+        let mut main = on(1);
+        let mut test = on(2);
+        assert_eq!(main.network_id(), 1);
+        main.add_block(issue(1..=5), miner()).unwrap();
+        assert_eq!(main.blocks[1].network_id, 1);
+        assert!(main.validate_chain());
+
+        assert_eq!(
+            test.accept_block(main.blocks[1].clone()),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::WrongNetwork {
+                    block_id: 1,
+                    network_id: 1
+                }
+            ))
+        );
+        assert_eq!(test.orphan_count(), 0);
+
+        // Relabelling a block changes its hash
+        let mut relabelled = main.blocks[1].clone();
+        relabelled.network_id = 2;
+        assert_ne!(relabelled.calculate_hash(), main.blocks[1].calculate_hash());
+        let json = serde_json::to_value(&main).unwrap();
+        assert_eq!(json["config"]["network_id"], 1);
+        assert_eq!(json["blocks"][1]["network_id"], 1);
+    }
+
+    #[test]
+    fn test_add_blocks_is_all_or_nothing() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let mut peer = fork(&local);
+        for first in (6..=25).step_by(5) {
+            peer.add_block(issue(first..first + 5), miner()).unwrap();
+        }
+        let before = local.latest_block().unwrap().hash;
+
+        let mut batch = peer.blocks[2..].to_vec();
+        batch[2].transactions[1].outputs[0].amount += 1;
+        assert_eq!(
+            local.add_blocks(batch),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::HashMismatch { block_id: 4 }
+            ))
+        );
+        assert_eq!(local.height(), 1);
+        assert_eq!(local.latest_block().unwrap().hash, before);
+        assert!(local.find_transaction(6).is_none());
+        assert_eq!(local.balance_of(&miner()), Ok(50));
+
+        local.add_blocks(peer.blocks[2..].to_vec()).unwrap();
+        assert_eq!(local.height(), 5);
+        assert_eq!(local.balance_of(&miner()), peer.balance_of(&miner()));
+        assert!(local.validate_chain());
+    }
+
+    #[test]
+    fn test_estimated_hashrate() {
+        let mut blockchain = chain();
+        for first in (1..=20).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        let blocks = blockchain.blocks.iter_mut();
+        for (block, (timestamp, difficulty)) in
+            blocks.zip([(0, 0), (10, 1), (20, 2), (30, 2), (50, 3)])
+        {
+            block.timestamp = timestamp;
+            block.target = difficulty::leading_zeros_target(difficulty);
+        }
+
+        // Just under 16^d hashes at d leading zeros: 255 + 4_095 over the
+        // last 30 seconds
+        assert_eq!(blockchain.estimated_hashrate(3), Some(4_350.0 / 30.0));
+        assert_eq!(
+            blockchain.estimated_hashrate(5),
+            Some((15.0 + 255.0 + 255.0 + 4_095.0) / 50.0)
+        );
+        assert_eq!(blockchain.estimated_hashrate(6), None);
+        assert_eq!(blockchain.estimated_hashrate(1), None);
+    }
+
+    #[test]
+    fn test_validate_headers() {
+        let mut blockchain = chain();
+        for first in (1..=15).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        blockchain.prune(1);
+
+        let mut headers = blockchain.headers();
+        assert_eq!(headers.len(), 4);
+        assert_eq!(chain().validate_headers(&headers), Ok(()));
+        assert_eq!(
+            chain().validate_headers(&[]),
+            Err(ValidationError::EmptyChain)
+        );
+
+        headers[2].previous_hash = Hash::ZERO;
+        assert_eq!(
+            chain().validate_headers(&headers),
+            Err(ValidationError::BrokenLink { block_id: 2 })
+        );
+    }
+
+    #[test]
+    fn test_expired_transactions_are_left_out() {
+        let clock = MockClock::new(1_700_000_000);
+        let mut blockchain = Blockchain::with_clock(config(), clock.clone());
+        let issuer = issuer();
+        let first = transfer_with_nonce(1, 0, &issuer, &address("Bob"), 10);
+        blockchain.submit_transaction(first.clone()).unwrap();
+        let mut expiring = transfer_with_nonce(2, 1, &issuer, &address("Alice"), 10);
+        expiring.expires_at = Some(1_700_000_050);
+        issuer.sign(&mut expiring);
+        blockchain.submit_transaction(expiring.clone()).unwrap();
+
+        clock.advance(100);
+        assert_eq!(
+            blockchain.add_block(vec![first, expiring], miner()),
+            Err(BlockchainError::Expired {
+                id: 2,
+                expires_at: 1_700_000_050
+            })
+        );
+
+        blockchain.flush_pending(miner()).unwrap();
+        let block = blockchain.latest_block().unwrap();
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(block.transactions[1].id, 1);
+        assert_eq!(blockchain.pending_count(), 0);
+        assert_eq!(blockchain.balance_of(&address("Alice")), Ok(0));
+    }
+
+    #[test]
+    fn test_transaction_pays_several_accounts() {
+        let mut blockchain = chain();
+        let alice = wallet(1);
+        blockchain
+            .add_block(
+                vec![transfer_with_nonce(1, 0, &issuer(), &alice.address(), 100)],
+                miner(),
+            )
+            .unwrap();
+
+        let split = |id, carol_gets| {
+            let mut tx = Transaction::new(
+                alice.address(),
+                vec![
+                    TxOutput::new(address("Bob"), 60),
+                    TxOutput::new(address("Carol"), carol_gets),
+                ],
+            );
+            tx.id = id;
+            alice.sign(&mut tx);
+            tx
+        };
+        assert_eq!(
+            blockchain.add_block(vec![split(2, 50)], miner()),
+            Err(BlockchainError::InsufficientFunds {
+                account: alice.address(),
+                available: 100,
+                required: 110,
+            })
+        );
+
+        blockchain.add_block(vec![split(2, 40)], miner()).unwrap();
+        assert_eq!(blockchain.balance_of(&alice.address()), Ok(0));
+        assert_eq!(blockchain.balance_of(&address("Bob")), Ok(60));
+        assert_eq!(blockchain.balance_of(&address("Carol")), Ok(40));
+        assert_eq!(blockchain.transaction_history(&address("Carol")).len(), 1);
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_more_work_beats_more_blocks() {
+        let start = 1_700_000_000;
+        let (local_clock, peer_clock) = (MockClock::new(start), MockClock::new(start));
+        let mut local = Blockchain::with_clock(retargeting_chain().config, local_clock.clone());
+        let mut peer = Blockchain::with_clock(local.config.clone(), peer_clock.clone());
+
+        // Slow blocks take the local difficulty down to 0, fast ones cut the
+        // peer's target to a quarter
+        for first in (1..=25).step_by(5) {
+            local_clock.advance(300);
+            local.add_block(issue(first..first + 5), miner()).unwrap();
+        }
+        for first in (1..=20).step_by(5) {
+            peer_clock.advance(1);
+            peer.add_block(issue(first..first + 5), miner()).unwrap();
+        }
+        assert_eq!(local.latest_block().unwrap().difficulty, 0);
+        assert_eq!(peer.latest_block().unwrap().target[0], 0x04);
+        // 2^256 / (target + 1) hashes each: 15 below 2^252, 3 below 2^254
+        // and 63 below 2^250
+        assert_eq!(local.total_work(), 1 + 2 * 15 + 2 * 3 + 1);
+        assert_eq!(peer.total_work(), 1 + 2 * 15 + 2 * 63);
+
+        assert_eq!(peer.try_replace_chain(local.blocks.clone()), Ok(false));
+        assert_eq!(local.try_replace_chain(peer.blocks.clone()), Ok(true));
+        assert_eq!(local.height(), 4);
+        assert!(local.validate_chain());
+    }
+
+    #[test]
+    fn test_block_byte_limit() {
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            max_block_bytes: 2_000,
+            ..config()
+        });
+        let issuer = issuer();
+        let transactions: Vec<Transaction> = (0..5)
+            .map(|nonce| {
+                let receiver = address(&format!("Receiver{}", nonce));
+                let mut tx = transfer_with_nonce(nonce + 1, nonce, &issuer, &receiver, 10);
+                tx.memo = Some(vec![b'x'; 200]);
+                issuer.sign(&mut tx);
+                tx
+            })
+            .collect();
+
+        assert!(matches!(
+            blockchain.add_block(transactions.clone(), miner()),
+            Err(BlockchainError::BlockTooLarge {
+                block_id: 1,
+                max: 2_000,
+                ..
+            })
+        ));
+
+        for tx in transactions {
+            blockchain.submit_transaction(tx).unwrap();
+        }
+        blockchain.flush_pending(miner()).unwrap();
+        let block = blockchain.latest_block().unwrap();
+        assert_eq!(block.transactions.len(), 1 + 3);
+        assert!(block.serialized_size() <= 2_000);
+        assert_eq!(blockchain.pending_count(), 2);
+        assert!(blockchain.validate_chain());
+    }
+
+    #[test]
+    fn test_competing_blocks_are_kept_on_branches() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let (mut peer_a, mut peer_b) = (fork(&local), fork(&local));
+        peer_a.add_block(issue(6..=10), miner()).unwrap();
+        peer_b
+            .add_block(issue(6..=10), wallet(1).address())
+            .unwrap();
+        peer_b
+            .add_block(issue(11..=15), wallet(1).address())
+            .unwrap();
+        let hash = |peer: &Blockchain, id: usize| peer.blocks[id].hash.unwrap();
+
+        assert_eq!(
+            local.accept_block(peer_a.blocks[2].clone()),
+            Ok(BlockStatus::OnChain)
+        );
+        assert_eq!(
+            local.accept_block(peer_b.blocks[2].clone()),
+            Ok(BlockStatus::OnBranch)
+        );
+        // Equal work, so the chain stays with the block it saw first
+        assert_eq!(local.best_tip(), Some(hash(&peer_a, 2)));
+        assert_eq!(local.tips(), vec![hash(&peer_a, 2), hash(&peer_b, 2)]);
+        assert!(local.branch_block(&hash(&peer_b, 2)).is_some());
+
+        assert_eq!(
+            local.accept_block(peer_b.blocks[3].clone()),
+            Ok(BlockStatus::OnChain)
+        );
+        assert_eq!(local.best_tip(), Some(hash(&peer_b, 3)));
+        assert_eq!(local.tips(), vec![hash(&peer_b, 3), hash(&peer_a, 2)]);
+        assert!(local.branch_block(&hash(&peer_a, 2)).is_some());
+        assert!(local.branch_block(&hash(&peer_b, 2)).is_none());
+        assert_eq!(local.balance_of(&wallet(1).address()), Ok(100));
+        assert!(local.validate_chain());
+    }
+
+    #[test]
+    fn test_forks_arriving_out_of_order_are_pieced_together() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let mut peer = fork(&local);
+        local.add_block(issue(6..=10), miner()).unwrap();
+        peer.add_block(issue(6..=10), wallet(1).address()).unwrap();
+        peer.add_block(issue(11..=15), wallet(1).address()).unwrap();
+        let replaced = local.blocks[2].hash.unwrap();
+
+        // The fork's second block shows up before its first
+        assert_eq!(
+            local.accept_block(peer.blocks[3].clone()),
+            Ok(BlockStatus::Orphan)
+        );
+        assert_eq!(
+            local.accept_block(peer.blocks[2].clone()),
+            Ok(BlockStatus::OnChain)
+        );
+        assert_eq!(local.best_tip(), peer.blocks[3].hash);
+        assert_eq!(local.orphan_count(), 0);
+        assert!(local.branch_block(&replaced).is_some());
+        assert_eq!(local.balance_of(&wallet(1).address()), Ok(100));
+        assert!(local.validate_chain());
+    }
+
+    #[test]
+    fn test_reorg_onto_an_invalid_branch_is_refused() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let mut peer = fork(&local);
+        local.add_block(issue(6..=10), miner()).unwrap();
+        peer.add_block(issue(6..=10), wallet(1).address()).unwrap();
+        peer.add_block(issue(11..=15), wallet(1).address()).unwrap();
+        let tip = local.best_tip();
+
+        // The fork's first block pays its miner more than the reward
+        let mut greedy = peer.blocks[2].clone();
+        greedy.transactions[0].outputs[0].amount = 1_000;
+        greedy.seal();
+        let mut next = peer.blocks[3].clone();
+        next.previous_hash = greedy.hash.unwrap();
+        next.seal();
+        let overpaid = BlockchainError::InvalidTransaction {
+            id: 2,
+            reason: "the coinbase should pay 50".to_string(),
+        };
+
+        assert_eq!(
+            local.accept_block(greedy.clone()),
+            Ok(BlockStatus::OnBranch)
+        );
+        assert_eq!(local.accept_block(next.clone()), Err(overpaid.clone()));
+        assert_eq!(local.best_tip(), tip);
+        assert!(local.branch_block(&next.hash.unwrap()).is_none());
+        assert_eq!(local.balance_of(&wallet(1).address()), Ok(0));
+
+        let mut candidate = peer.blocks.clone();
+        candidate[2] = greedy;
+        candidate[3] = next;
+        assert_eq!(local.try_replace_chain(candidate), Err(overpaid));
+        assert_eq!(local.best_tip(), tip);
+        assert_eq!(local.balance_of(&miner()), Ok(100));
+        assert!(local.validate_chain());
+    }
+
+    #[test]
+    fn test_popping_a_block_drops_what_was_built_on_it() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let mut peer = fork(&local);
+        local.add_block(issue(6..=10), miner()).unwrap();
+        for first in (6..=20).step_by(5) {
+            peer.add_block(issue(first..first + 5), wallet(1).address())
+                .unwrap();
+        }
+        let branch = peer.blocks[2].hash.unwrap();
+        assert_eq!(
+            local.accept_block(peer.blocks[2].clone()),
+            Ok(BlockStatus::OnBranch)
+        );
+        let mut stray = peer.blocks[3].clone();
+        stray.previous_hash = branch;
+        stray.transactions.pop();
+        stray.seal();
+        local.hold_orphan(stray);
+
+        local.pop_block().unwrap();
+        local.pop_block().unwrap();
+        assert!(local.branch_block(&branch).is_none());
+        assert_eq!(local.orphan_count(), 0);
+        assert_eq!(local.tips(), vec![local.blocks[0].hash.unwrap()]);
+
+        // What built on the dropped branch has to wait for it again
+        assert_eq!(
+            local.accept_block(peer.blocks[3].clone()),
+            Ok(BlockStatus::Orphan)
+        );
+    }
+
+    #[test]
+    fn test_get_blocks_in_range() {
+        let mut blockchain = chain();
+        for first in (1..=25).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        let ids = |blocks: Vec<&Block>| blocks.iter().map(|block| block.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(blockchain.get_blocks(2, 4)), vec![2, 3, 4]);
+        assert_eq!(ids(blockchain.get_blocks(3, 3)), vec![3]);
+        assert_eq!(ids(blockchain.get_blocks(4, 100)), vec![4, 5]);
+        assert!(blockchain.get_blocks(6, 10).is_empty());
+        assert!(blockchain.get_blocks(3, 2).is_empty());
+    }
+
+    #[test]
+    fn test_first_invalid_block() {
+        let mut blockchain = chain();
+        for first in (1..=20).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        assert_eq!(blockchain.len(), 5);
+        assert_eq!(blockchain.first_invalid_block(), None);
+
+        blockchain.blocks[3].transactions[2].outputs[0].amount = 1_000;
+        assert_eq!(blockchain.first_invalid_block(), Some(3));
+
+        blockchain.blocks[2].id = 7;
+        assert_eq!(blockchain.first_invalid_block(), Some(2));
+    }
+
+    #[test]
+    fn test_signatures_follow_the_configured_scheme() {
+        let issuer = Wallet::from_secret_key_with(&[9; 32], SignatureAlgorithm::Secp256k1).unwrap();
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            issuer: Some(issuer.address()),
+            signature_algorithm: SignatureAlgorithm::Secp256k1,
+            ..BlockchainConfig::default()
+        });
+        let id = blockchain
+            .add_block(vec![transfer(1, &issuer, &address("Bob"), 10)], miner())
+            .unwrap();
+        assert_eq!(id, 1);
+
+        // Signed with ed25519, which this chain does not accept
+        let ed25519 = wallet(1);
+        assert_eq!(
+            blockchain.add_block(vec![transfer(2, &ed25519, &address("Bob"), 10)], miner()),
+            Err(BlockchainError::InvalidSignature { id: 2 })
+        );
+    }
+
+    #[test]
+    fn test_block_of_transaction_hash() {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+
+        let tx = issue([8]).remove(0);
+        assert_eq!(blockchain.block_of_transaction_hash(&tx.hash()), Some(2));
+        let coinbase = &blockchain.blocks[1].transactions[0];
+        assert_eq!(
+            blockchain.block_of_transaction_hash(&coinbase.hash()),
+            Some(1)
+        );
+        assert_eq!(blockchain.block_of_transaction_hash(&Hash::ZERO), None);
+
+        blockchain.pop_block();
+        assert_eq!(blockchain.block_of_transaction_hash(&tx.hash()), None);
+    }
+
+    #[test]
+    fn test_chain_proof_links_back_to_the_anchor() {
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            difficulty: 1,
+            ..config()
+        });
+        for first in (1..=25).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        let anchor = blockchain.blocks[1].header();
+
+        let proof = blockchain.chain_proof(1, 4);
+        assert_eq!(
+            proof.iter().map(|header| header.id).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        let hasher = blockchain.hasher();
+        assert!(verify_chain_proof(&anchor, &proof, hasher));
+        assert!(!verify_chain_proof(
+            &blockchain.blocks[0].header(),
+            &proof,
+            hasher
+        ));
+        assert!(!verify_chain_proof(&anchor, &proof[1..], hasher));
+
+        let mut forged = proof.clone();
+        forged[1].timestamp += 1;
+        assert!(!verify_chain_proof(&anchor, &forged, hasher));
+
+        assert!(blockchain.chain_proof(4, 1).is_empty());
+        assert!(blockchain.chain_proof(1, 6).is_empty());
+    }
+
+    #[test]
+    fn test_chain_at_the_last_id_cannot_grow() {
+        let mut blockchain = chain();
+        let previous_hash = blockchain.blocks[0].hash.unwrap();
+        let mut last = Block::new(u64::MAX, previous_hash);
+        last.seal();
+        blockchain.blocks.push(last);
+
+        assert_eq!(
+            blockchain.add_block(issue(1..=5), miner()),
+            Err(BlockchainError::ChainTooLong)
+        );
+        assert_eq!(blockchain.height(), u64::MAX);
+    }
+
+    #[test]
+    fn test_all_balances_match_balance_of() {
+        let (alice, bob) = (wallet(1), wallet(2));
+        let mut blockchain = two_per_block();
+        blockchain
+            .add_block(
+                vec![
+                    transfer(1, &issuer(), &alice.address(), 100),
+                    transfer_with_nonce(2, 1, &issuer(), &bob.address(), 30),
+                ],
+                miner(),
+            )
+            .unwrap();
+        blockchain
+            .add_block(
+                vec![
+                    transfer_with_fee(3, &alice, &bob.address(), 40, 5),
+                    transfer(4, &bob, &address("Carol"), 25),
+                ],
+                wallet(3).address(),
+            )
+            .unwrap();
+
+        let balances = blockchain.all_balances();
+        for account in blockchain.accounts() {
+            let expected = blockchain.balance_of(&account).unwrap();
+            assert_eq!(balances[&account], i128::from(expected), "{}", account);
+        }
+        assert_eq!(balances[&alice.address()], 55);
+        assert_eq!(balances[&wallet(3).address()], 55);
+
+        // Spent more than it had, in a block nobody checked
+        blockchain.blocks[2].transactions[1].outputs[0].amount = 500;
+        assert_eq!(blockchain.all_balances()[&alice.address()], -405);
+    }
+
+    #[test]
+    fn test_chain_tag_changes_every_hash() {
+        let tagged = |tag: &str| {
+            let clock = MockClock::new(1_700_000_000);
+            let config = BlockchainConfig {
+                chain_tag: tag.to_string(),
+                ..config()
+            };
+            let mut blockchain = Blockchain::with_clock(config, clock);
+            blockchain.add_block(issue(1..=5), miner()).unwrap();
+            blockchain
+        };
+        let (plain, a, b) = (tagged(""), tagged("alpha"), tagged("beta"));
+        for (left, right) in [(&plain, &a), (&a, &b)] {
+            for (ours, theirs) in left.iter().zip(right) {
+                assert_ne!(ours.hash, theirs.hash);
+            }
+        }
+        assert_eq!(plain.blocks[1].hash, Some(plain.blocks[1].calculate_hash()));
+
+        let mut swapped = tagged("alpha");
+        swapped.blocks = b.blocks.clone();
+        assert_eq!(
+            swapped.validate_chain_detailed(),
+            Err(ValidationError::HashMismatch { block_id: 0 })
+        );
+        assert!(a.validate_chain() && b.validate_chain());
+        let previous = a.blocks[0].hash.unwrap();
+        assert_eq!(a.blocks[1].verify(&previous, a.hasher()), Ok(()));
+
+        // Proofs are checked with the tag too
+        let proof = a.chain_proof(0, 1);
+        let anchor = a.blocks[0].header();
+        let alpha = TaggedHasher::wrap("alpha", Arc::new(Sha256Hasher));
+        assert!(verify_chain_proof(&anchor, &proof, alpha.as_ref()));
+        assert!(!verify_chain_proof(&anchor, &proof, &Sha256Hasher));
+        assert!(!verify_chain_proof(&anchor, &proof, b.hasher()));
+
+        // The tag comes back with the config
+        let restored: Blockchain =
+            serde_json::from_str(&serde_json::to_string(&a).unwrap()).unwrap();
+        assert!(restored.validate_chain());
+    }
+
+    #[test]
+    fn test_rebuild_from_a_height() {
+        let mut blockchain = chain();
+        for first in (1..=20).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+        let old: Vec<Option<Hash>> = blockchain.iter().map(|block| block.hash).collect();
+
+        // Receiver6 to Receiver10 move up a block, the rest are left out
+        blockchain
+            .rebuild_from(2, vec![issue([6]), issue(7..=10)])
+            .unwrap();
+        assert_eq!(blockchain.height(), 3);
+        assert!(blockchain.validate_chain());
+        let new: Vec<Option<Hash>> = blockchain.iter().map(|block| block.hash).collect();
+        assert_eq!(new[..2], old[..2]);
+        assert!(new[2..].iter().zip(&old[2..]).all(|(new, old)| new != old));
+        assert_eq!(blockchain.find_transaction(7).unwrap().0, 3);
+        assert!(blockchain.find_transaction(11).is_none());
+        assert_eq!(blockchain.balance_of(&miner()), Ok(150));
+        assert!(blockchain.branch_block(&old[4].unwrap()).is_some());
+
+        // A batch that does not fit leaves the chain alone
+        assert_eq!(
+            blockchain.rebuild_from(2, vec![issue(6..=10), issue([6])]),
+            Err(BlockchainError::DuplicateTransaction { id: 6 })
+        );
+        assert_eq!(
+            blockchain
+                .iter()
+                .map(|block| block.hash)
+                .collect::<Vec<_>>(),
+            new
+        );
+        assert_eq!(
+            blockchain.rebuild_from(0, Vec::new()),
+            Err(BlockchainError::HeightOutOfRange { height: 0, tip: 3 })
+        );
+    }
+
+    #[test]
+    fn test_empty_blocks_when_allowed() {
+        let mut blockchain = chain();
+        assert_eq!(blockchain.flush_pending(miner()), Ok(None));
+
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            allow_empty_blocks: true,
+            ..config()
+        });
+        assert_eq!(blockchain.add_block(Vec::new(), miner()), Ok(1));
+        assert_eq!(blockchain.flush_pending(miner()), Ok(Some(2)));
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+
+        let empty = &blockchain.blocks[2];
+        assert_eq!(empty.transactions.len(), 1);
+        assert_eq!(
+            empty.merkle_root(),
+            crate::merkle::merkle_root(&[empty.transactions[0].hash()])
+        );
+        assert_eq!(blockchain.balance_of(&miner()), Ok(150));
+        assert!(blockchain.validate_chain());
+
+        // The same blocks are too small for a chain that does not allow them
+        let mut strict = chain();
+        strict.blocks = blockchain.blocks.clone();
+        assert_eq!(
+            strict.validate_chain_detailed(),
+            Err(ValidationError::WrongTransactionCount {
+                block_id: 1,
+                count: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_confirmations() {
+        let mut blockchain = chain();
+        for first in (1..=15).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+
+        assert_eq!(blockchain.confirmations(3), 0);
+        assert_eq!(blockchain.confirmations(1), 2);
+        assert_eq!(blockchain.confirmations(0), 3);
+        assert!(blockchain.is_confirmed(1, 2));
+        assert!(!blockchain.is_confirmed(2, 2));
+        assert!(blockchain.is_confirmed(3, 0));
+        assert!(!blockchain.is_confirmed(4, 0));
+
+        assert_eq!(blockchain.transaction_confirmations(7), Some(1));
+        assert_eq!(blockchain.transaction_confirmations(99), None);
+    }
+
+    #[test]
+    fn test_block_reward_halves() {
+        let mut blockchain = Blockchain::new(BlockchainConfig {
+            halving_interval: 2,
+            allow_empty_blocks: true,
+            ..config()
+        });
+        assert_eq!(blockchain.current_reward(), 50);
+
+        let mut rewards = Vec::new();
+        for _ in 0..6 {
+            rewards.push(blockchain.current_reward());
+            blockchain.add_block(Vec::new(), miner()).unwrap();
+        }
+        assert_eq!(rewards, vec![50, 25, 25, 12, 12, 6]);
+        assert_eq!(blockchain.balance_of(&miner()), Ok(130));
+        assert_eq!(blockchain.total_supply(), Ok(130));
+
+        // Six halvings take 50 down to nothing
+        assert_eq!(blockchain.block_reward_at(11), 1);
+        assert_eq!(blockchain.block_reward_at(12), 0);
+        assert_eq!(blockchain.block_reward_at(u64::MAX), 0);
+        while blockchain.height() < 12 {
+            blockchain.add_block(Vec::new(), miner()).unwrap();
+        }
+        let coinbase = &blockchain.latest_block().unwrap().transactions[0];
+        assert_eq!(coinbase.quantity(), Ok(0));
+        assert!(blockchain.validate_chain());
+
+        let steady = chain();
+        assert_eq!(steady.block_reward_at(u64::MAX), 50);
+    }
+
+    #[test]
+    fn test_check_block_without_appending() {
+        let mut local = chain();
+        local.add_block(issue(1..=5), miner()).unwrap();
+        let mut peer = fork(&local);
+        peer.add_block(issue(6..=10), miner()).unwrap();
+        peer.add_block(issue(11..=15), miner()).unwrap();
+
+        let next = peer.blocks[2].clone();
+        assert_eq!(local.check_block(&next), Ok(()));
+        assert_eq!(local.height(), 1);
+
+        assert_eq!(
+            local.check_block(&peer.blocks[3]),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::UnexpectedId {
+                    block_id: 3,
+                    expected: 2
+                }
+            ))
+        );
+
+        local.accept_block(next).unwrap();
+        assert_eq!(local.check_block(&peer.blocks[3]), Ok(()));
+    }
+
+    #[test]
+    fn test_proof_of_authority_takes_the_authority_key() {
+        let (authority, impostor) = (wallet(5), wallet(6));
+        let poa = || ProofOfAuthority::new(vec![authority.public_key()]);
+        let config = BlockchainConfig {
+            target_adjustment_interval: 0,
+            ..config()
+        };
+        let mut blockchain =
+            Blockchain::with_consensus(config.clone(), poa().with_signer(wallet(5)));
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+
+        let block = &blockchain.blocks[1];
+        assert_eq!(block.seal.as_ref().unwrap().signer, authority.public_key());
+        assert_eq!(block.nonce, 0);
+        assert!(blockchain.validate_chain());
+
+        let mut forged = blockchain.blocks[1].clone();
+        poa()
+            .with_signer(wallet(6))
+            .seal(&mut forged, &Sha256Hasher);
+        assert_eq!(forged.seal.as_ref().unwrap().signer, impostor.public_key());
+        blockchain.blocks[1] = forged.clone();
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::InvalidSeal { block_id: 1 })
+        );
+
+        // Nor does a block that was only mined
+        blockchain.pop_block();
+        forged.seal = None;
+        ProofOfWork.seal(&mut forged, &Sha256Hasher);
+        assert_eq!(
+            blockchain.accept_block(forged),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::InvalidSeal { block_id: 1 }
+            ))
+        );
+        assert_eq!(blockchain.height(), 0);
+    }
+
+    #[test]
+    fn test_proof_of_authority_outlives_a_reload() {
+        let authority = wallet(5);
+        let poa = || ProofOfAuthority::new(vec![authority.public_key()]);
+        let config = BlockchainConfig {
+            target_adjustment_interval: 0,
+            ..config()
+        };
+
+        // Without a key nothing gets sealed, so nothing gets pushed
+        let mut blockchain = Blockchain::with_consensus(config.clone(), poa());
+        for tx in issue(1..=5) {
+            blockchain.submit_transaction(tx).unwrap();
+        }
+        assert_eq!(
+            blockchain.flush_pending(miner()),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::InvalidSeal { block_id: 1 }
+            ))
+        );
+        assert_eq!(blockchain.height(), 0);
+        assert_eq!(blockchain.pending_count(), 5);
+
+        // Signing looks for no nonce, so a chain asking for work gets none
+        let mut demanding = Blockchain::with_consensus(
+            BlockchainConfig {
+                difficulty: 3,
+                ..config.clone()
+            },
+            poa().with_signer(wallet(5)),
+        );
+        assert_eq!(
+            demanding.add_block(issue(1..=5), miner()),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::InsufficientWork { block_id: 1 }
+            ))
+        );
+        assert_eq!(demanding.height(), 0);
+        assert!(demanding.validate_chain());
+
+        blockchain.set_consensus(poa().with_signer(wallet(5)));
+        assert_eq!(blockchain.flush_pending(miner()), Ok(Some(1)));
+
+        // The config names no authorities, yet the reload still wants a seal
+        let json = serde_json::to_string(&blockchain).unwrap();
+        let mut loaded: Blockchain = serde_json::from_str(&json).unwrap();
+        assert!(loaded.validate_chain());
+        let mut mined = loaded.unmined_block(issue(6..=6), miner()).unwrap();
+        ProofOfWork.seal(&mut mined, &Sha256Hasher);
+        assert_eq!(
+            loaded.accept_block(mined),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::InvalidSeal { block_id: 2 }
+            ))
+        );
+        assert_eq!(
+            loaded.add_block(issue(6..=6), miner()),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::InvalidSeal { block_id: 2 }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_authorities_take_turns_sealing() {
+        let (first, second) = (wallet(5), wallet(6));
+        let config = BlockchainConfig {
+            target_adjustment_interval: 0,
+            authorities: vec![first.public_key(), second.public_key()],
+            ..config()
+        };
+        let poa = || ProofOfAuthority::new(config.authorities.clone());
+        let mut blockchain =
+            Blockchain::with_consensus(config.clone(), poa().with_signer(wallet(6)));
+        assert_eq!(
+            blockchain.expected_proposer(1),
+            Some(&second.public_key()[..])
+        );
+
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        for tx in issue(6..=6) {
+            blockchain.submit_transaction(tx).unwrap();
+        }
+        assert_eq!(
+            blockchain.flush_pending(miner()),
+            Err(BlockchainError::InvalidBlock(
+                ValidationError::WrongProposer { block_id: 2 }
+            ))
+        );
+        assert_eq!(blockchain.height(), 1);
+        assert_eq!(blockchain.pending_count(), 1);
+
+        blockchain.set_consensus(poa().with_signer(wallet(5)));
+        assert_eq!(blockchain.flush_pending(miner()), Ok(Some(2)));
+        assert!(blockchain.validate_chain());
+
+        // A block from an authority out of its turn does not validate either
+        let mut forged = blockchain.blocks[2].clone();
+        poa()
+            .with_signer(wallet(6))
+            .seal(&mut forged, blockchain.hasher.as_ref());
+        blockchain.blocks[2] = forged;
+        assert_eq!(
+            blockchain.validate_chain_detailed(),
+            Err(ValidationError::WrongProposer { block_id: 2 })
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of block timestamps, in seconds since the Unix epoch.
+pub trait Clock: Send + Sync {
+    fn now_secs(&self) -> u64;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_secs(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a
+/// test can keep one and hand another to a chain.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    secs: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new(secs: u64) -> Self {
+        Self {
+            secs: Arc::new(AtomicU64::new(secs)),
+        }
+    }
+
+    pub fn set(&self, secs: u64) {
+        self.secs.store(secs, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.secs.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_secs(&self) -> u64 {
+        self.secs.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_is_shared_between_clones() {
+        let clock = MockClock::new(100);
+        let handle = clock.clone();
+        handle.advance(5);
+        assert_eq!(clock.now_secs(), 105);
+        handle.set(7);
+        assert_eq!(clock.now_secs(), 7);
+    }
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use crate::address::Address;
+use crate::hash::Hash;
+use crate::signature::SignatureAlgorithm;
+
+pub const DEFAULT_MIN_TRANSACTIONS_PER_BLOCK: usize = 1;
+pub const DEFAULT_MAX_TRANSACTIONS_PER_BLOCK: usize = 5;
+pub const DEFAULT_BLOCK_REWARD: u64 = 50;
+pub const DEFAULT_TARGET_BLOCK_TIME_SECS: u64 = 10;
+pub const DEFAULT_TARGET_ADJUSTMENT_INTERVAL: u64 = 10;
+pub const DEFAULT_MAX_FUTURE_DRIFT_SECS: u64 = 2 * 60 * 60;
+pub const DEFAULT_MAX_MEMO_BYTES: usize = 256;
+pub const DEFAULT_MAX_BLOCK_BYTES: usize = 1024 * 1024;
+pub const DEFAULT_MAX_ORPHANS: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlockchainConfig {
+    /// Bounds on the transactions in a block, not counting the coinbase.
+    pub min_transactions_per_block: usize,
+    pub max_transactions_per_block: usize,
+    /// Lets a block hold nothing but its coinbase whatever the minimum, so
+    /// blocks keep coming while nothing is pending.
+    pub allow_empty_blocks: bool,
+    pub issuer: Option<Address>,
+    pub difficulty: usize,
+    pub block_reward: u64,
+    /// Blocks between halvings of the block reward; 0 keeps it the same.
+    pub halving_interval: u64,
+    pub target_block_time_secs: u64,
+    /// Number of blocks between difficulty retargets; 0 disables retargeting.
+    pub target_adjustment_interval: u64,
+    /// How far ahead of the local clock a block timestamp may be.
+    pub max_future_drift_secs: u64,
+    pub max_memo_bytes: usize,
+    /// Limit on a block's size in bincode, the encoding peers exchange.
+    pub max_block_bytes: usize,
+    /// Blocks held back waiting for their parent; past this the ones held
+    /// longest make way.
+    pub max_orphans: usize,
+    /// Balances paid out by the genesis block of a new chain.
+    pub initial_allocations: Vec<(Address, u64)>,
+    /// Blocks that have to follow a coinbase before its reward can be spent.
+    pub coinbase_maturity: u64,
+    /// Stamped into every block so blocks from one network do not validate
+    /// on another.
+    pub network_id: u32,
+    /// Goes in front of everything a block hash is taken over, so chains
+    /// holding the same data under different tags do not share hashes. Empty
+    /// leaves hashes as they are.
+    pub chain_tag: String,
+    /// Hash the genesis block has to have, when the chain's genesis is known
+    /// ahead of time.
+    pub genesis_hash: Option<Hash>,
+    /// How transactions on the chain are signed.
+    pub signature_algorithm: SignatureAlgorithm,
+    /// Public keys taking turns at sealing blocks under proof of authority:
+    /// the block at height `h` has to be sealed by `authorities[h % len]`.
+    /// Empty leaves sealing to the chain's consensus alone.
+    pub authorities: Vec<Vec<u8>>,
+}
+
+impl Default for BlockchainConfig {
+    fn default() -> Self {
+        Self {
+            min_transactions_per_block: DEFAULT_MIN_TRANSACTIONS_PER_BLOCK,
+            max_transactions_per_block: DEFAULT_MAX_TRANSACTIONS_PER_BLOCK,
+            allow_empty_blocks: false,
+            issuer: None,
+            difficulty: 0,
+            block_reward: DEFAULT_BLOCK_REWARD,
+            halving_interval: 0,
+            target_block_time_secs: DEFAULT_TARGET_BLOCK_TIME_SECS,
+            target_adjustment_interval: DEFAULT_TARGET_ADJUSTMENT_INTERVAL,
+            max_future_drift_secs: DEFAULT_MAX_FUTURE_DRIFT_SECS,
+            max_memo_bytes: DEFAULT_MAX_MEMO_BYTES,
+            max_block_bytes: DEFAULT_MAX_BLOCK_BYTES,
+            max_orphans: DEFAULT_MAX_ORPHANS,
+            initial_allocations: Vec::new(),
+            coinbase_maturity: 0,
+            network_id: 0,
+            chain_tag: String::new(),
+            genesis_hash: None,
+            signature_algorithm: SignatureAlgorithm::Ed25519,
+            authorities: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block::{AuthoritySeal, Block};
+use crate::hash::BlockHasher;
+use crate::signature::SignatureAlgorithm;
+use crate::wallet::Wallet;
+
+/// The rule deciding who may add a block. A chain seals its blocks with it
+/// and checks every block it validates against it, on top of the hash, link
+/// and difficulty checks all blocks go through.
+pub trait Consensus: Send + Sync {
+    /// Finishes a block whose contents and target are settled, setting its
+    /// hash as `hasher` computes it.
+    fn seal(&self, block: &mut Block, hasher: &dyn BlockHasher);
+
+    /// Whether the block carries the seal the rule asks for. Its hash has
+    /// already been checked.
+    fn verify(&self, block: &Block) -> bool;
+
+    /// What gets saved with the chain so it is read back under the same rule.
+    fn kind(&self) -> ConsensusKind;
+}
+
+/// A consensus rule as saved with a chain. Signing keys are never saved, so
+/// a proof of authority read back can verify blocks but not seal them until
+/// it is given a signer again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsensusKind {
+    ProofOfWork,
+    ProofOfAuthority {
+        authorities: Vec<Vec<u8>>,
+        algorithm: SignatureAlgorithm,
+    },
+}
+
+impl ConsensusKind {
+    pub fn build(&self) -> Box<dyn Consensus> {
+        match self {
+            ConsensusKind::ProofOfWork => Box::new(ProofOfWork),
+            ConsensusKind::ProofOfAuthority {
+                authorities,
+                algorithm,
+            } => Box::new(ProofOfAuthority::new(authorities.clone()).with_algorithm(*algorithm)),
+        }
+    }
+}
+
+/// Mining: the nonce is searched until the hash meets the block's target.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProofOfWork;
+
+impl Consensus for ProofOfWork {
+    fn seal(&self, block: &mut Block, hasher: &dyn BlockHasher) {
+        block.mine_to_target(block.target, hasher);
+    }
+
+    fn verify(&self, block: &Block) -> bool {
+        block.meets_difficulty()
+    }
+
+    fn kind(&self) -> ConsensusKind {
+        ConsensusKind::ProofOfWork
+    }
+}
+
+/// Blocks are signed by one of a fixed set of authorities instead of mined.
+/// Sealing never looks for a nonce, so on a config with a difficulty or
+/// that retargets, the chain refuses the blocks it seals.
+pub struct ProofOfAuthority {
+    authorities: Vec<Vec<u8>>,
+    algorithm: SignatureAlgorithm,
+    signer: Option<Wallet>,
+}
+
+impl ProofOfAuthority {
+    /// Accepts blocks signed with ed25519 by any of `authorities`, given as
+    /// public keys. Without a signer it can verify blocks but not seal them.
+    pub fn new(authorities: Vec<Vec<u8>>) -> Self {
+        Self {
+            authorities,
+            algorithm: SignatureAlgorithm::Ed25519,
+            signer: None,
+        }
+    }
+
+    pub fn with_algorithm(mut self, algorithm: SignatureAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Seals blocks with `signer`'s key, which should be one of the
+    /// authorities for the blocks to verify.
+    pub fn with_signer(mut self, signer: Wallet) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+}
+
+impl Consensus for ProofOfAuthority {
+    fn seal(&self, block: &mut Block, hasher: &dyn BlockHasher) {
+        let hash = block.calculate_hash_with(hasher);
+        block.hash = Some(hash);
+        block.seal = self.signer.as_ref().map(|signer| AuthoritySeal {
+            signer: signer.public_key(),
+            signature: signer.sign_message(hash.as_bytes()),
+        });
+    }
+
+    fn verify(&self, block: &Block) -> bool {
+        let (Some(hash), Some(seal)) = (block.hash, &block.seal) else {
+            return false;
+        };
+        self.authorities.contains(&seal.signer)
+            && self
+                .algorithm
+                .scheme()
+                .verify(&seal.signer, hash.as_bytes(), &seal.signature)
+    }
+
+    fn kind(&self) -> ConsensusKind {
+        ConsensusKind::ProofOfAuthority {
+            authorities: self.authorities.clone(),
+            algorithm: self.algorithm,
+        }
+    }
+}
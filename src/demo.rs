@@ -0,0 +1,76 @@
+use crate::blockchain::Blockchain;
+use crate::clock::MockClock;
+use crate::config::BlockchainConfig;
+use crate::error::BlockchainError;
+use crate::transaction::Transaction;
+use crate::wallet::Wallet;
+
+/// Number of blocks [`demo`] mines on top of the genesis block.
+pub const DEMO_BLOCKS: u64 = 20;
+
+const DEMO_START_SECS: u64 = 1_700_000_000;
+const DEMO_BLOCK_SECS: u64 = 10;
+const DEMO_ACCOUNTS: u8 = 4;
+
+/// Builds the same chain on every run: fixed keys, and a clock that starts at
+/// a fixed time and moves ten seconds per block. The issuer funds four
+/// accounts, which then pass coins around.
+pub fn demo() -> Result<Blockchain, BlockchainError> {
+    let issuer = Wallet::from_secret_key(&[1; 32]);
+    let accounts: Vec<Wallet> = (0..DEMO_ACCOUNTS)
+        .map(|seed| Wallet::from_secret_key(&[seed + 2; 32]))
+        .collect();
+    let miner = Wallet::from_secret_key(&[0xff; 32]).address();
+
+    let clock = MockClock::new(DEMO_START_SECS);
+    let mut blockchain = Blockchain::with_clock(
+        BlockchainConfig {
+            issuer: Some(issuer.address()),
+            difficulty: 1,
+            ..BlockchainConfig::default()
+        },
+        clock.clone(),
+    );
+
+    for id in 1..=DEMO_BLOCKS {
+        let index = (id % u64::from(DEMO_ACCOUNTS)) as usize;
+        let (from, destination, quantity) = if id <= u64::from(DEMO_ACCOUNTS) {
+            (&issuer, accounts[index].address(), 100)
+        } else {
+            let next = (index + 1) % accounts.len();
+            (&accounts[index], accounts[next].address(), id)
+        };
+
+        let mut transaction = Transaction::simple(from.address(), destination, quantity);
+        transaction.id = id;
+        transaction.nonce = blockchain.next_nonce(&from.address());
+        from.sign(&mut transaction);
+
+        clock.advance(DEMO_BLOCK_SECS);
+        blockchain.add_block(vec![transaction], miner)?;
+    }
+    Ok(blockchain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo_is_reproducible() {
+        let blockchain = demo().unwrap();
+        assert_eq!(blockchain.height(), DEMO_BLOCKS);
+        assert!(blockchain.validate_chain());
+
+        let tip = blockchain.latest_block().unwrap();
+        assert_eq!(
+            tip.timestamp,
+            DEMO_START_SECS + DEMO_BLOCKS * DEMO_BLOCK_SECS
+        );
+        assert_eq!(
+            tip.hash.unwrap().to_string(),
+            "0db871f139faf902303c4038474b97a59f90652eb7d153cd287943832ec7d8aa"
+        );
+        assert_eq!(demo().unwrap().latest_block().unwrap().hash, tip.hash);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::block::Block;
+use crate::config::BlockchainConfig;
+use crate::hash::Hash;
+
+/// Furthest a single retarget may move the target, either way.
+const MAX_ADJUSTMENT_FACTOR: u64 = 4;
+
+/// The easiest target, met by every hash but the very last one.
+pub const MAX_TARGET: [u8; 32] = [0xff; 32];
+
+/// Target the next block appended after `blocks` has to be mined below.
+pub(crate) fn next_target(blocks: &[Block], config: &BlockchainConfig) -> [u8; 32] {
+    let tip = match blocks.last() {
+        Some(block) => block,
+        None => return leading_zeros_target(config.difficulty),
+    };
+    let height = blocks.len() - 1;
+    let window_start = height
+        .checked_sub(config.target_adjustment_interval as usize)
+        .map(|index| blocks[index].timestamp);
+    target_after(
+        height as u64,
+        &tip.target,
+        tip.timestamp,
+        window_start,
+        config,
+    )
+}
+
+/// Same as [`next_target`] for callers that only keep what they know of the
+/// tip and the timestamp of the block one adjustment interval below it.
+pub(crate) fn target_after(
+    height: u64,
+    tip_target: &[u8; 32],
+    tip_timestamp: u64,
+    window_start: Option<u64>,
+    config: &BlockchainConfig,
+) -> [u8; 32] {
+    let interval = config.target_adjustment_interval;
+    let start = match window_start {
+        Some(start) if interval > 0 && height >= interval => start,
+        _ => return leading_zeros_target(config.difficulty),
+    };
+    if !height.is_multiple_of(interval) {
+        return *tip_target;
+    }
+
+    let actual = tip_timestamp.saturating_sub(start);
+    let expected = config.target_block_time_secs.saturating_mul(interval);
+    retarget(tip_target, actual, expected)
+}
+
+/// Leading hex zeros every hash below `target` has, which is what a block's
+/// `difficulty` records.
+pub(crate) fn target_difficulty(target: &[u8; 32]) -> usize {
+    // The highest hash meeting the target is the one just below it
+    let mut highest = *target;
+    for byte in highest.iter_mut().rev() {
+        let (value, borrowed) = byte.overflowing_sub(1);
+        *byte = value;
+        if !borrowed {
+            return Hash::from_bytes(highest).leading_zero_nibbles();
+        }
+    }
+    // Nothing is below a zero target
+    64
+}
+
+/// Target for a (fractional) difficulty, scaled so that it takes
+/// `difficulty` hashes on average to get below it. Difficulties up to 1 give
+/// [`MAX_TARGET`].
+pub fn difficulty_to_target(difficulty: f64) -> [u8; 32] {
+    if difficulty.is_nan() || difficulty <= 1.0 {
+        return MAX_TARGET;
+    }
+    // The target is 2^256 / difficulty; its bytes are the base-256 digits of
+    // 1 / difficulty, and multiplying by 256 keeps every digit exact.
+    let mut rest = 1.0 / difficulty;
+    let mut target = [0u8; 32];
+    for byte in &mut target {
+        rest *= 256.0;
+        *byte = rest as u8;
+        rest = rest.fract();
+    }
+    target
+}
+
+/// Inverse of [`difficulty_to_target`], up to the precision of an `f64`.
+pub fn target_to_difficulty(target: &[u8; 32]) -> f64 {
+    let fraction = target
+        .iter()
+        .rev()
+        .fold(0.0, |rest, &byte| (rest + f64::from(byte)) / 256.0);
+    if fraction == 0.0 {
+        f64::INFINITY
+    } else {
+        (1.0 / fraction).max(1.0)
+    }
+}
+
+/// Whether the hash, read as a big-endian number, is below the target.
+pub fn meets_target(hash: &Hash, target: &[u8; 32]) -> bool {
+    hash.as_bytes() < target
+}
+
+/// The target that counts as `difficulty` leading hex zeros: any hash below
+/// it has at least that many.
+pub(crate) fn leading_zeros_target(difficulty: usize) -> [u8; 32] {
+    match i32::try_from(difficulty) {
+        Ok(0) => MAX_TARGET,
+        Ok(nibbles) if nibbles < 64 => difficulty_to_target(16f64.powi(nibbles)),
+        _ => [0; 32],
+    }
+}
+
+/// Hashes it takes on average to get below `target`: 2^256 / (target + 1),
+/// rounded down. Targets too low for that to fit give `u128::MAX`.
+pub(crate) fn target_work(target: &[u8; 32]) -> u128 {
+    // Below 2^128 the quotient is at least 2^128
+    if target[..16].iter().all(|&byte| byte == 0) {
+        return u128::MAX;
+    }
+    if *target == MAX_TARGET {
+        return 1;
+    }
+    // 2^256 / (target + 1) is one more than (2^256 - 1 - target) / (target + 1),
+    // and the right hand side fits in 256 bits
+    let mut divisor = [0u8; 33];
+    divisor[1..].copy_from_slice(target);
+    for byte in divisor.iter_mut().rev() {
+        let (value, carried) = byte.overflowing_add(1);
+        *byte = value;
+        if !carried {
+            break;
+        }
+    }
+
+    let mut remainder = [0u8; 33];
+    let mut quotient: u128 = 0;
+    for bit in 0..256 {
+        let next = (!target[bit / 8] >> (7 - bit % 8)) & 1;
+        let mut carry = next;
+        for byte in remainder.iter_mut().rev() {
+            let shifted = (*byte << 1) | carry;
+            carry = *byte >> 7;
+            *byte = shifted;
+        }
+        quotient <<= 1;
+        if remainder >= divisor {
+            let mut borrow = 0;
+            for (digit, &subtrahend) in remainder.iter_mut().zip(&divisor).rev() {
+                let (value, under) = digit.overflowing_sub(subtrahend);
+                let (value, under_again) = value.overflowing_sub(borrow);
+                *digit = value;
+                borrow = u8::from(under || under_again);
+            }
+            quotient |= 1;
+        }
+    }
+    quotient.saturating_add(1)
+}
+
+/// Scales the target by how long the window took against how long it should
+/// have, so blocks coming twice as fast halve it. One retarget moves the
+/// target by at most [`MAX_ADJUSTMENT_FACTOR`] either way, and never past
+/// [`MAX_TARGET`].
+pub(crate) fn retarget(target: &[u8; 32], actual_secs: u64, expected_secs: u64) -> [u8; 32] {
+    if expected_secs == 0 {
+        return *target;
+    }
+    let lowest = scale(target, 1, MAX_ADJUSTMENT_FACTOR);
+    let highest = scale(target, MAX_ADJUSTMENT_FACTOR, 1);
+    scale(target, actual_secs, expected_secs).clamp(lowest, highest)
+}
+
+/// `target * numerator / denominator`, rounded down and capped at
+/// [`MAX_TARGET`]. The product gets eight more bytes than the target, which
+/// is all a `u64` factor can add.
+fn scale(target: &[u8; 32], numerator: u64, denominator: u64) -> [u8; 32] {
+    let mut product = [0u8; 40];
+    let mut carry: u128 = 0;
+    for (digit, &byte) in product[8..].iter_mut().zip(target).rev() {
+        let value = u128::from(byte) * u128::from(numerator) + carry;
+        *digit = value as u8;
+        carry = value >> 8;
+    }
+    for digit in product[..8].iter_mut().rev() {
+        *digit = carry as u8;
+        carry >>= 8;
+    }
+
+    let mut quotient = [0u8; 40];
+    let mut remainder: u128 = 0;
+    for (digit, &byte) in quotient.iter_mut().zip(&product) {
+        let value = (remainder << 8) | u128::from(byte);
+        *digit = (value / u128::from(denominator)) as u8;
+        remainder = value % u128::from(denominator);
+    }
+    if quotient[..8].iter().any(|&byte| byte != 0) {
+        return MAX_TARGET;
+    }
+    quotient[8..].try_into().expect("32 bytes are left")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_must_be_below_the_target() {
+        // 2^256 / 384 = 0x00aaaa..., cut off where the f64 runs out of digits
+        let target = difficulty_to_target(384.0);
+        assert_eq!(target[..3], [0x00, 0xaa, 0xaa]);
+        let last = target.iter().rposition(|&byte| byte != 0).unwrap();
+        assert!(last < 31);
+
+        let mut below = target;
+        below[last] -= 1;
+        below[last + 1..].fill(0xff);
+        let mut above = target;
+        above[31] = 1;
+
+        assert!(meets_target(&Hash::from_bytes(below), &target));
+        assert!(!meets_target(&Hash::from_bytes(target), &target));
+        assert!(!meets_target(&Hash::from_bytes(above), &target));
+    }
+
+    #[test]
+    fn test_targets_and_float_difficulty() {
+        assert_eq!(difficulty_to_target(0.5), MAX_TARGET);
+        assert_eq!(difficulty_to_target(1.0), MAX_TARGET);
+        assert_eq!(target_to_difficulty(&MAX_TARGET), 1.0);
+
+        let mut two_zeros = [0u8; 32];
+        two_zeros[0] = 1;
+        assert_eq!(leading_zeros_target(2), two_zeros);
+        assert_eq!(target_to_difficulty(&two_zeros), 256.0);
+
+        // Between two and three leading zeros
+        let target = difficulty_to_target(1_000.0);
+        assert!(target < two_zeros && target > leading_zeros_target(3));
+        assert!((target_to_difficulty(&target) - 1_000.0).abs() < 1e-9);
+        assert_eq!(target_to_difficulty(&[0; 32]), f64::INFINITY);
+    }
+
+    /// The target `2^exponent`.
+    fn power_of_two(exponent: usize) -> [u8; 32] {
+        let mut target = [0u8; 32];
+        target[31 - exponent / 8] = 1 << (exponent % 8);
+        target
+    }
+
+    #[test]
+    fn test_fast_blocks_lower_the_target() {
+        let target = power_of_two(240);
+        assert_eq!(retarget(&target, 50, 100), power_of_two(239));
+        assert_eq!(retarget(&target, 75, 100), scale(&target, 3, 4));
+        assert!(retarget(&target, 75, 100) < target);
+    }
+
+    #[test]
+    fn test_slow_blocks_raise_the_target() {
+        let target = power_of_two(240);
+        assert_eq!(retarget(&target, 200, 100), power_of_two(241));
+        assert_eq!(retarget(&MAX_TARGET, 200, 100), MAX_TARGET);
+    }
+
+    #[test]
+    fn test_on_target_keeps_the_target() {
+        let target = difficulty_to_target(1_000.0);
+        assert_eq!(retarget(&target, 100, 100), target);
+        assert_eq!(retarget(&target, 100, 0), target);
+    }
+
+    #[test]
+    fn test_adjustment_is_clamped_to_four_times() {
+        let target = power_of_two(240);
+        assert_eq!(retarget(&target, 0, 1_000_000), power_of_two(238));
+        assert_eq!(retarget(&target, 1_000_000, 1), power_of_two(242));
+    }
+
+    #[test]
+    fn test_work_is_inversely_proportional_to_the_target() {
+        assert_eq!(target_work(&MAX_TARGET), 1);
+        assert_eq!(target_work(&power_of_two(255)), 1);
+        assert_eq!(target_work(&power_of_two(240)), 65_535);
+
+        let mut just_below = power_of_two(240);
+        just_below[1] = 0;
+        just_below[2..].fill(0xff);
+        assert_eq!(target_work(&just_below), 65_536);
+        // A quarter of the target takes four times the hashes
+        let quarter = scale(&just_below, 1, 4);
+        assert_eq!(target_work(&quarter), 4 * 65_536);
+
+        assert_eq!(target_work(&power_of_two(128)), u128::MAX);
+        assert_eq!(target_work(&[0; 32]), u128::MAX);
+    }
+
+    #[test]
+    fn test_difficulty_follows_the_target() {
+        assert_eq!(target_difficulty(&MAX_TARGET), 0);
+        assert_eq!(target_difficulty(&power_of_two(247)), 2);
+        assert_eq!(target_difficulty(&[0; 32]), 64);
+        for difficulty in 0..5 {
+            assert_eq!(
+                target_difficulty(&leading_zeros_target(difficulty)),
+                difficulty
+            );
+            // Halfway down to the next step still counts as the same
+            let target = power_of_two(255 - 4 * difficulty);
+            assert_eq!(target_difficulty(&target), difficulty);
+            assert!(target <= leading_zeros_target(difficulty));
+        }
+    }
+}
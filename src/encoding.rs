@@ -0,0 +1,116 @@
+use sha2::{Digest, Sha256};
+
+use crate::hash::Hash;
+
+/// Where an [`Encoder`] puts its bytes.
+pub(crate) trait Sink {
+    fn write(&mut self, bytes: &[u8]);
+}
+
+impl Sink for Vec<u8> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+impl Sink for Sha256 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+}
+
+/// Lays fields out as bytes for hashing and signing. Integers are big-endian
+/// and anything of variable length is prefixed with its length, so two
+/// different field lists never produce the same bytes.
+///
+/// The bytes either pile up in a buffer or, from [`Encoder::hashing`], go
+/// straight into SHA256 so nothing the size of the input gets allocated.
+#[derive(Debug, Default)]
+pub(crate) struct Encoder<S = Vec<u8>> {
+    sink: S,
+}
+
+impl Encoder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.sink)
+    }
+}
+
+impl Encoder<Sha256> {
+    pub(crate) fn hashing() -> Self {
+        Self {
+            sink: Sha256::new(),
+        }
+    }
+
+    pub(crate) fn finish_hash(&mut self) -> Hash {
+        Hash::from_bytes(std::mem::take(&mut self.sink).finalize().into())
+    }
+}
+
+impl<S: Sink> Encoder<S> {
+    pub(crate) fn u64(&mut self, value: u64) -> &mut Self {
+        self.sink.write(&value.to_be_bytes());
+        self
+    }
+
+    /// Data whose size is fixed by its type, such as a hash or an address.
+    pub(crate) fn raw(&mut self, bytes: &[u8]) -> &mut Self {
+        self.sink.write(bytes);
+        self
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.u64(bytes.len() as u64).raw(bytes)
+    }
+
+    pub(crate) fn optional_u64(&mut self, value: Option<u64>) -> &mut Self {
+        match value {
+            Some(value) => self.raw(&[1]).u64(value),
+            None => self.raw(&[0]),
+        }
+    }
+
+    pub(crate) fn optional_bytes(&mut self, bytes: Option<&[u8]>) -> &mut Self {
+        match bytes {
+            Some(bytes) => self.raw(&[1]).bytes(bytes),
+            None => self.raw(&[0]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_prefixes_keep_fields_apart() {
+        let joined = Encoder::new().bytes(b"ab").bytes(b"c").finish();
+        let split = Encoder::new().bytes(b"a").bytes(b"bc").finish();
+        assert_ne!(joined, split);
+        assert_eq!(
+            Encoder::new().u64(1).optional_u64(None).finish(),
+            vec![0, 0, 0, 0, 0, 0, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn test_hashing_matches_hashing_the_buffer() {
+        fn encode<S: Sink>(encoder: &mut Encoder<S>) {
+            encoder.u64(7).bytes(b"memo").optional_u64(Some(3));
+        }
+        let mut buffered = Encoder::new();
+        encode(&mut buffered);
+        let mut hashing = Encoder::hashing();
+        encode(&mut hashing);
+
+        assert_eq!(
+            hashing.finish_hash(),
+            crate::hash::sha256(buffered.finish())
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::fmt;
+
+use crate::address::Address;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockchainError {
+    WrongTransactionCount {
+        min: usize,
+        max: usize,
+        got: usize,
+    },
+    EmptyChain,
+    MissingPreviousHash,
+    Io(String),
+    EmptyFile,
+    Deserialize(String),
+    InvalidChain(ValidationError),
+    GenesisMismatch,
+    InvalidSignature {
+        id: u64,
+    },
+    DuplicateTransaction {
+        id: u64,
+    },
+    InvalidTransaction {
+        id: u64,
+        reason: String,
+    },
+    InsufficientFunds {
+        account: Address,
+        available: u64,
+        required: u64,
+    },
+    Overflow,
+    MissingOutput {
+        id: u64,
+    },
+    BadNonce {
+        id: u64,
+        expected: u64,
+        got: u64,
+    },
+    InvalidBlock(ValidationError),
+    MissingCoinbase {
+        block_id: u64,
+    },
+    MemoTooLarge {
+        id: u64,
+        size: usize,
+        max: usize,
+    },
+    TimeLocked {
+        id: u64,
+        not_before: u64,
+    },
+    CorruptFile(String),
+    ImmatureCoinbase {
+        id: u64,
+    },
+    Expired {
+        id: u64,
+        expires_at: u64,
+    },
+    BlockTooLarge {
+        block_id: u64,
+        size: usize,
+        max: usize,
+    },
+    ChainTooLong,
+    HeightOutOfRange {
+        height: u64,
+        tip: u64,
+    },
+    PrunedHistory {
+        height: u64,
+    },
+}
+
+impl fmt::Display for BlockchainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockchainError::WrongTransactionCount { min, max, got } => write!(
+                f,
+                "a block must contain between {} and {} transactions, got {}",
+                min, max, got
+            ),
+            BlockchainError::EmptyChain => write!(f, "the blockchain has no blocks"),
+            BlockchainError::MissingPreviousHash => {
+                write!(f, "the latest block has no hash to link against")
+            }
+            BlockchainError::Io(reason) => write!(f, "i/o error: {}", reason),
+            BlockchainError::EmptyFile => write!(f, "the chain file is empty"),
+            BlockchainError::Deserialize(reason) => {
+                write!(f, "could not decode the chain: {}", reason)
+            }
+            BlockchainError::InvalidChain(reason) => {
+                write!(f, "the chain failed validation: {}", reason)
+            }
+            BlockchainError::GenesisMismatch => {
+                write!(f, "the chains do not share the same genesis block")
+            }
+            BlockchainError::InvalidSignature { id } => {
+                write!(f, "transaction {} has an invalid signature", id)
+            }
+            BlockchainError::DuplicateTransaction { id } => {
+                write!(f, "transaction id {} is already in use", id)
+            }
+            BlockchainError::InvalidTransaction { id, reason } => {
+                write!(f, "transaction {} is invalid: {}", id, reason)
+            }
+            BlockchainError::InsufficientFunds {
+                account,
+                available,
+                required,
+            } => write!(
+                f,
+                "account {} has {} but the transaction needs {}",
+                account, available, required
+            ),
+            BlockchainError::Overflow => write!(f, "an amount does not fit in 64 bits"),
+            BlockchainError::BadNonce { id, expected, got } => write!(
+                f,
+                "transaction {} has nonce {} but the origin's next nonce is {}",
+                id, got, expected
+            ),
+            BlockchainError::MissingOutput { id } => write!(
+                f,
+                "transaction {} spends an output that does not exist or is already spent",
+                id
+            ),
+            BlockchainError::InvalidBlock(reason) => {
+                write!(f, "the block was rejected: {}", reason)
+            }
+            BlockchainError::MissingCoinbase { block_id } => {
+                write!(f, "block {} does not start with a coinbase", block_id)
+            }
+            BlockchainError::MemoTooLarge { id, size, max } => write!(
+                f,
+                "transaction {} carries a {} byte memo, more than the {} allowed",
+                id, size, max
+            ),
+            BlockchainError::TimeLocked { id, not_before } => write!(
+                f,
+                "transaction {} cannot be included before {}",
+                id, not_before
+            ),
+            BlockchainError::Expired { id, expires_at } => {
+                write!(f, "transaction {} expired at {}", id, expires_at)
+            }
+            BlockchainError::BlockTooLarge {
+                block_id,
+                size,
+                max,
+            } => write!(
+                f,
+                "block {} takes {} bytes, more than the {} allowed",
+                block_id, size, max
+            ),
+            BlockchainError::ChainTooLong => {
+                write!(f, "the chain has used up every block id")
+            }
+            BlockchainError::HeightOutOfRange { height, tip } => write!(
+                f,
+                "height {} is not between 1 and the tip at {}",
+                height, tip
+            ),
+            BlockchainError::PrunedHistory { height } => write!(
+                f,
+                "the blocks below {} were pruned and cannot be replaced",
+                height
+            ),
+            BlockchainError::CorruptFile(reason) => {
+                write!(f, "the compressed chain file is corrupt: {}", reason)
+            }
+            BlockchainError::ImmatureCoinbase { id } => write!(
+                f,
+                "transaction {} spends a coinbase reward that has not matured",
+                id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BlockchainError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    EmptyChain,
+    InvalidGenesis,
+    UnexpectedId { block_id: u64, expected: u64 },
+    MissingHash { block_id: u64 },
+    HashMismatch { block_id: u64 },
+    InsufficientWork { block_id: u64 },
+    UnexpectedDifficulty { block_id: u64, expected: usize },
+    UnexpectedTarget { block_id: u64 },
+    BrokenLink { block_id: u64 },
+    TimestampRegression { block_id: u64 },
+    WrongTransactionCount { block_id: u64, count: usize },
+    FutureTimestamp { block_id: u64 },
+    CheckpointMismatch { block_id: u64 },
+    WrongNetwork { block_id: u64, network_id: u32 },
+    InvalidSeal { block_id: u64 },
+    WrongProposer { block_id: u64 },
+    PrunedWithTransactions { block_id: u64 },
+    UnexpectedPrune { block_id: u64 },
+}
+
+impl ValidationError {
+    /// Id of the block the error was found at. A block carrying the wrong id
+    /// is reported by where it sits in the chain.
+    pub fn block_id(&self) -> Option<u64> {
+        match *self {
+            ValidationError::EmptyChain => None,
+            ValidationError::InvalidGenesis => Some(0),
+            ValidationError::UnexpectedId { expected, .. } => Some(expected),
+            ValidationError::MissingHash { block_id }
+            | ValidationError::HashMismatch { block_id }
+            | ValidationError::InsufficientWork { block_id }
+            | ValidationError::UnexpectedDifficulty { block_id, .. }
+            | ValidationError::UnexpectedTarget { block_id }
+            | ValidationError::BrokenLink { block_id }
+            | ValidationError::TimestampRegression { block_id }
+            | ValidationError::WrongTransactionCount { block_id, .. }
+            | ValidationError::FutureTimestamp { block_id }
+            | ValidationError::CheckpointMismatch { block_id }
+            | ValidationError::WrongNetwork { block_id, .. }
+            | ValidationError::InvalidSeal { block_id }
+            | ValidationError::WrongProposer { block_id }
+            | ValidationError::PrunedWithTransactions { block_id }
+            | ValidationError::UnexpectedPrune { block_id } => Some(block_id),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::EmptyChain => write!(f, "the chain has no blocks"),
+            ValidationError::InvalidGenesis => {
+                write!(f, "the first block is not a valid genesis block")
+            }
+            ValidationError::UnexpectedId { block_id, expected } => {
+                write!(
+                    f,
+                    "block {} sits where block {} belongs",
+                    block_id, expected
+                )
+            }
+            ValidationError::MissingHash { block_id } => {
+                write!(f, "block {} has no hash", block_id)
+            }
+            ValidationError::HashMismatch { block_id } => {
+                write!(f, "block {} does not match its stored hash", block_id)
+            }
+            ValidationError::InsufficientWork { block_id } => {
+                write!(f, "block {} does not meet its difficulty", block_id)
+            }
+            ValidationError::UnexpectedDifficulty { block_id, expected } => write!(
+                f,
+                "block {} should have been mined at difficulty {}",
+                block_id, expected
+            ),
+            ValidationError::UnexpectedTarget { block_id } => write!(
+                f,
+                "block {} was not mined to the target the retarget asks for",
+                block_id
+            ),
+            ValidationError::BrokenLink { block_id } => {
+                write!(f, "block {} does not link to the block before it", block_id)
+            }
+            ValidationError::TimestampRegression { block_id } => {
+                write!(f, "block {} is older than the block before it", block_id)
+            }
+            ValidationError::WrongTransactionCount { block_id, count } => write!(
+                f,
+                "block {} holds {} transactions, outside the configured bounds",
+                block_id, count
+            ),
+            ValidationError::FutureTimestamp { block_id } => {
+                write!(f, "block {} is timestamped too far in the future", block_id)
+            }
+            ValidationError::CheckpointMismatch { block_id } => {
+                write!(f, "block {} does not match the checkpoint", block_id)
+            }
+            ValidationError::WrongNetwork {
+                block_id,
+                network_id,
+            } => write!(f, "block {} belongs to network {}", block_id, network_id),
+            ValidationError::InvalidSeal { block_id } => {
+                write!(
+                    f,
+                    "block {} is not sealed the way consensus requires",
+                    block_id
+                )
+            }
+            ValidationError::WrongProposer { block_id } => {
+                write!(f, "block {} is sealed out of turn", block_id)
+            }
+            ValidationError::PrunedWithTransactions { block_id } => {
+                write!(
+                    f,
+                    "block {} is pruned but still holds transactions",
+                    block_id
+                )
+            }
+            ValidationError::UnexpectedPrune { block_id } => {
+                write!(
+                    f,
+                    "block {} is pruned where its transactions are needed",
+                    block_id
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
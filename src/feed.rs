@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tungstenite::{Error as WsError, Message, WebSocket};
+
+use crate::shared::SharedBlockchain;
+
+/// How long a connection waits for a block before checking on the client.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a client gets to send its upgrade request.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Subscribers = Arc<Mutex<HashMap<u64, Sender<String>>>>;
+
+/// Pushes every block added to the chain, as JSON text frames, to each
+/// WebSocket client connected at the time. Clients only listen; anything
+/// they send other than a close is ignored.
+pub struct BlockFeed {
+    listener: TcpListener,
+    subscribers: Subscribers,
+    next_id: AtomicU64,
+}
+
+impl BlockFeed {
+    pub fn bind(addr: impl ToSocketAddrs, blockchain: &SharedBlockchain) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let subscribers: Subscribers = Arc::default();
+
+        let publish = Arc::clone(&subscribers);
+        blockchain.write(|blockchain| {
+            blockchain.on_block_added(Box::new(move |block| {
+                let json = serde_json::to_string(block).unwrap_or_default();
+                let mut subscribers = publish.lock().expect("subscriber lock poisoned");
+                // Sending fails once the connection has gone away
+                subscribers.retain(|_, subscriber| subscriber.send(json.clone()).is_ok());
+            }))
+        });
+
+        Ok(Self {
+            listener,
+            subscribers,
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers
+            .lock()
+            .expect("subscriber lock poisoned")
+            .len()
+    }
+
+    /// Accepts clients until the listener fails, serving each on its own
+    /// thread. The handshake happens there too, so a client that never
+    /// sends its upgrade request holds up nobody else.
+    pub fn run(&self) {
+        for stream in self.listener.incoming().flatten() {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            let subscribers = Arc::clone(&self.subscribers);
+            thread::spawn(move || {
+                let Ok(socket) = handshake(stream) else {
+                    return;
+                };
+                let (sender, receiver) = mpsc::channel();
+                subscribers
+                    .lock()
+                    .expect("subscriber lock poisoned")
+                    .insert(id, sender);
+                serve(socket, receiver);
+                subscribers
+                    .lock()
+                    .expect("subscriber lock poisoned")
+                    .remove(&id);
+            });
+        }
+    }
+}
+
+fn handshake(stream: TcpStream) -> io::Result<WebSocket<TcpStream>> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let socket = tungstenite::accept(stream).map_err(io::Error::other)?;
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    Ok(socket)
+}
+
+fn serve(mut socket: WebSocket<TcpStream>, blocks: Receiver<String>) {
+    loop {
+        match blocks.recv_timeout(POLL_INTERVAL) {
+            Ok(json) => {
+                if socket.send(Message::text(json)).is_err() {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                let _ = socket.close(None);
+                return;
+            }
+        }
+
+        match socket.read() {
+            Ok(_) => {}
+            Err(WsError::Io(err))
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            // Covers a close from the client, which tungstenite has answered
+            Err(_) => return,
+        }
+    }
+}
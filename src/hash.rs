@@ -0,0 +1,170 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::encoding::Encoder;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hash([u8; 32]);
+
+impl Hash {
+    pub const ZERO: Hash = Hash([0; 32]);
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Hash(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// First eight hex characters, for display.
+    pub fn short(&self) -> String {
+        hex::encode(&self.0[..4])
+    }
+
+    pub fn leading_zero_nibbles(&self) -> usize {
+        let mut count = 0;
+        for byte in self.0 {
+            if byte == 0 {
+                count += 2;
+                continue;
+            }
+            if byte < 0x10 {
+                count += 1;
+            }
+            break;
+        }
+        count
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash({})", self)
+    }
+}
+
+impl FromStr for Hash {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(s, &mut bytes)?;
+        Ok(Hash(bytes))
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+pub(crate) fn sha256(data: impl AsRef<[u8]>) -> Hash {
+    Hash(Sha256::digest(data).into())
+}
+
+/// Hash function used to seal blocks.
+pub trait BlockHasher: Send + Sync {
+    fn hash(&self, data: &[u8]) -> [u8; 32];
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256Hasher;
+
+impl BlockHasher for Sha256Hasher {
+    fn hash(&self, data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+}
+
+/// Puts a chain's tag, length-prefixed, in front of everything `inner`
+/// hashes, so chains with different tags never produce the same block hash.
+pub struct TaggedHasher {
+    tag: Vec<u8>,
+    inner: Arc<dyn BlockHasher>,
+}
+
+impl TaggedHasher {
+    /// `inner` itself when the tag is empty, keeping untagged hashes as they
+    /// were.
+    pub fn wrap(tag: &str, inner: Arc<dyn BlockHasher>) -> Arc<dyn BlockHasher> {
+        if tag.is_empty() {
+            return inner;
+        }
+        Arc::new(TaggedHasher {
+            tag: tag.as_bytes().to_vec(),
+            inner,
+        })
+    }
+}
+
+impl BlockHasher for TaggedHasher {
+    fn hash(&self, data: &[u8]) -> [u8; 32] {
+        self.inner
+            .hash(&Encoder::new().bytes(&self.tag).raw(data).finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_round_trip() {
+        let hash = sha256("simple-blockchain");
+        let text = hash.to_string();
+
+        assert_eq!(text.len(), 64);
+        assert_eq!(text.parse::<Hash>().unwrap(), hash);
+        assert_eq!(
+            serde_json::from_str::<Hash>(&serde_json::to_string(&hash).unwrap()).unwrap(),
+            hash
+        );
+    }
+
+    #[test]
+    fn test_invalid_hex_is_rejected() {
+        assert!("0".parse::<Hash>().is_err());
+        assert!("zz".repeat(32).parse::<Hash>().is_err());
+    }
+
+    #[test]
+    fn test_zero_hash() {
+        assert_eq!(Hash::ZERO.to_string(), "0".repeat(64));
+        assert_eq!(Hash::ZERO.leading_zero_nibbles(), 64);
+    }
+
+    #[test]
+    fn test_leading_zero_nibbles() {
+        let mut bytes = [0xff; 32];
+        bytes[0] = 0x00;
+        bytes[1] = 0x0a;
+        assert_eq!(Hash::from_bytes(bytes).leading_zero_nibbles(), 3);
+    }
+
+    #[test]
+    fn test_sha256_hasher_matches_sha256() {
+        let expected: Hash = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+            .parse()
+            .unwrap();
+        assert_eq!(Hash::from_bytes(Sha256Hasher.hash(b"abc")), expected);
+        assert_eq!(sha256("abc"), expected);
+    }
+}
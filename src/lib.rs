@@ -0,0 +1,58 @@
+mod address;
+mod block;
+mod blockchain;
+mod clock;
+mod config;
+mod consensus;
+mod demo;
+mod difficulty;
+mod encoding;
+mod error;
+#[cfg(feature = "server")]
+mod feed;
+mod hash;
+mod mempool;
+mod merkle;
+mod node;
+mod replay;
+#[cfg(feature = "server")]
+mod server;
+mod shared;
+mod signature;
+mod stats;
+mod storage;
+mod transaction;
+mod utxo;
+mod view;
+mod wallet;
+
+#[cfg(test)]
+mod test_support;
+
+pub use address::Address;
+pub use block::{
+    verify_chain_proof, AuthoritySeal, Block, BlockBuilder, BlockHeader, BlockSummary,
+};
+pub use blockchain::{BlockStatus, Blockchain};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use config::BlockchainConfig;
+pub use consensus::{Consensus, ConsensusKind, ProofOfAuthority, ProofOfWork};
+pub use demo::{demo, DEMO_BLOCKS};
+pub use difficulty::{difficulty_to_target, meets_target, target_to_difficulty, MAX_TARGET};
+pub use error::{BlockchainError, ValidationError};
+#[cfg(feature = "server")]
+pub use feed::BlockFeed;
+pub use hash::{BlockHasher, Hash, Sha256Hasher, TaggedHasher};
+pub use mempool::Mempool;
+pub use merkle::verify_merkle_proof;
+pub use node::{Node, PeerMessage};
+pub use replay::LoggedTransaction;
+#[cfg(feature = "server")]
+pub use server::{RpcError, RpcRequest, RpcResponse, RpcServer};
+pub use shared::SharedBlockchain;
+pub use signature::{Ed25519Scheme, Secp256k1Scheme, SignatureAlgorithm, SignatureScheme};
+pub use stats::ChainStats;
+pub use transaction::Transaction;
+pub use utxo::{TxInput, TxOutput, UtxoSet};
+pub use view::ChainView;
+pub use wallet::Wallet;
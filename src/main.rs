@@ -1,225 +1,172 @@
-use sha2::{Sha256, Digest};
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::collections::HashMap;
-
-#[derive(Debug, Clone)]
-struct Transaction {
-    id: u64,
-    origin: String,
-    destination: String,
-    quantity: u64,
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use simple_blockchain::{demo, Address, Blockchain, BlockchainConfig, Transaction, Wallet};
+
+const CHAIN_FILE: &str = "chain.json";
+const WALLET_FILE: &str = "wallets.json";
+const ISSUER: &str = "issuer";
+const MINER: &str = "miner";
+
+const USAGE: &str = "usage: simple-blockchain [--dir <path>] <command>
+
+commands:
+    new                          create a fresh chain
+    add-tx <origin> <dest> <qty> queue a signed transfer
+    mine [miner]                 seal pending transactions into a block
+    validate                     check the whole chain
+    show <id>                    print a block
+    balance <name>               print the balance of a wallet
+    demo                         print a chain that comes out the same every run";
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+/// Named signing keys, stored next to the chain as hex encoded secrets.
+#[derive(Default)]
+struct Keystore {
+    wallets: BTreeMap<String, String>,
 }
 
-#[derive(Debug, Clone)]
-struct Block {
-    id: u64,
-    timestamp: u64,
-    transactions: Vec<Transaction>,
-    previous_hash: String,
-    hash: Option<String>,
-}
-
-impl Block {
-    fn new(id: u64, previous_hash: String) -> Self {
-        Self {
-            id,
-            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
-            transactions: Vec::new(),
-            previous_hash,
-            hash: None,
+impl Keystore {
+    fn load(path: &Path) -> CliResult<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
         }
+        let wallets = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(Self { wallets })
     }
 
-    fn add_transaction(&mut self, transaction: Transaction) {
-        if self.transactions.len() < 5 {
-            self.transactions.push(transaction);
-            if self.transactions.len() == 5 {
-                self.hash = Some(self.calculate_hash());
-            }
-        }
+    fn save(&self, path: &Path) -> CliResult<()> {
+        fs::write(path, serde_json::to_string_pretty(&self.wallets)?)?;
+        Ok(())
     }
 
-    fn calculate_hash(&self) -> String {
-        let mut hasher = Sha256::new();
-        let data = format!(
-            "{}{}{:?}{}",
-            self.id, self.timestamp, self.transactions, self.previous_hash
-        );
-        hasher.update(data);
-        hex::encode(hasher.finalize())
-    }
-}
-
-struct Blockchain {
-    blocks: HashMap<u64, Block>,
-    latest_block: Option<u64>,
-}
-
-impl Blockchain {
-    fn new() -> Self {
-        let mut genesis_block = Block::new(0, String::from("0"));
-        genesis_block.hash = Some(genesis_block.calculate_hash());
-
-        let mut blockchain = Self {
-            blocks: HashMap::new(),
-            latest_block: Some(0),
+    fn get(&self, name: &str) -> CliResult<Option<Wallet>> {
+        let Some(secret) = self.wallets.get(name) else {
+            return Ok(None);
         };
-        blockchain.blocks.insert(0, genesis_block);
-        blockchain
-    }
-
-    fn add_block(&mut self, transactions: Vec<Transaction>) {
-        if transactions.len() != 5 {
-            panic!("A block must contain exactly 5 transactions.");
-        }
-
-        let latest_id = self.latest_block.unwrap();
-        let previous_hash = self.blocks[&latest_id].hash.clone().unwrap();
-        let mut block = Block::new(latest_id + 1, previous_hash);
-
-        for transaction in transactions {
-            block.add_transaction(transaction);
-        }
-
-        self.blocks.insert(block.id, block.clone());
-        self.latest_block = Some(block.id);
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(secret, &mut bytes)?;
+        Ok(Some(Wallet::from_secret_key(&bytes)))
     }
 
-    fn get_block_by_id(&self, id: u64) -> Option<&Block> {
-        self.blocks.get(&id)
-    }
-
-    fn validate_chain(&self) -> bool {
-        let mut previous_hash = String::from("0");
-
-        for id in 0..=self.latest_block.unwrap() {
-            let block = &self.blocks[&id];
-
-            if block.hash.is_none() || block.hash.as_ref().unwrap() != &block.calculate_hash() {
-                return false;
-            }
-
-            if block.previous_hash != previous_hash {
-                return false;
-            }
-
-            previous_hash = block.hash.clone().unwrap();
+    /// Looks up a wallet by name, creating it on first use.
+    fn get_or_create(&mut self, name: &str) -> CliResult<Wallet> {
+        if let Some(wallet) = self.get(name)? {
+            return Ok(wallet);
         }
-
-        true
+        let wallet = Wallet::generate();
+        self.wallets
+            .insert(name.to_string(), hex::encode(wallet.secret_key()));
+        Ok(wallet)
     }
 }
 
 fn main() {
-    let mut blockchain = Blockchain::new();
-    let mut transaction_id = 1;
-
-    for block_id in 1..=20 {
-        let transactions: Vec<Transaction> = (0..5)
-            .map(|_| {
-                let transaction = Transaction {
-                    id: transaction_id,
-                    origin: format!("User{}", transaction_id),
-                    destination: format!("User{}", transaction_id + 1),
-                    quantity: transaction_id * 10,
-                };
-                transaction_id += 1;
-                transaction
-            })
-            .collect();
-
-        blockchain.add_block(transactions);
-        println!("Added block with ID: {}", block_id);
-    }
-
-    if blockchain.validate_chain() {
-        println!("The blockchain is valid.");
-    } else {
-        println!("The blockchain is not valid.");
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        eprintln!("error: {}", err);
+        process::exit(1);
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_block_creation() {
-        let block = Block::new(1, String::from("0"));
-        assert_eq!(block.id, 1);
-        assert_eq!(block.transactions.len(), 0);
-    }
-
-    #[test]
-    fn test_transaction_addition() {
-        let mut block = Block::new(1, String::from("0"));
-        let transaction = Transaction {
-            id: 1,
-            origin: "Alice".to_string(),
-            destination: "Bob".to_string(),
-            quantity: 50,
-        };
-        block.add_transaction(transaction.clone());
-        assert_eq!(block.transactions.len(), 1);
-        assert_eq!(block.transactions[0].id, 1);
-    }
-
-    #[test]
-    fn test_block_hashing() {
-        let mut block = Block::new(1, String::from("0"));
-        for i in 1..=5 {
-            let transaction = Transaction {
-                id: i,
-                origin: format!("Sender{}", i),
-                destination: format!("Receiver{}", i),
-                quantity: i * 10,
+fn run(args: &[String]) -> CliResult<()> {
+    let (dir, args) = match args {
+        [flag, dir, rest @ ..] if flag == "--dir" => (PathBuf::from(dir), rest),
+        _ => (PathBuf::from("."), args),
+    };
+    let chain_path = dir.join(CHAIN_FILE);
+    let wallet_path = dir.join(WALLET_FILE);
+
+    match args {
+        [command] if command == "new" => {
+            let mut keystore = Keystore::default();
+            let issuer = keystore.get_or_create(ISSUER)?;
+            let blockchain = Blockchain::new(BlockchainConfig {
+                issuer: Some(issuer.address()),
+                ..BlockchainConfig::default()
+            });
+
+            fs::create_dir_all(&dir)?;
+            blockchain.save_to_file(&chain_path)?;
+            keystore.save(&wallet_path)?;
+            println!("Created a new chain in {}", chain_path.display());
+        }
+        [command, origin, destination, quantity] if command == "add-tx" => {
+            let mut blockchain = Blockchain::load_from_file(&chain_path)?;
+            let mut keystore = Keystore::load(&wallet_path)?;
+            let sender = keystore
+                .get(origin)?
+                .ok_or_else(|| format!("unknown wallet {}", origin))?;
+            let destination = keystore.get_or_create(destination)?.address();
+
+            let mut transaction =
+                Transaction::simple(sender.address(), destination, quantity.parse()?);
+            transaction.nonce = blockchain.next_nonce(&sender.address());
+            sender.sign(&mut transaction);
+
+            blockchain.submit_transaction(transaction)?;
+            if let Some(queued) = blockchain.pending_transactions().last() {
+                println!("Queued {}", queued);
+            }
+            blockchain.save_to_file(&chain_path)?;
+            keystore.save(&wallet_path)?;
+        }
+        [command, rest @ ..] if command == "mine" && rest.len() <= 1 => {
+            let mut blockchain = Blockchain::load_from_file(&chain_path)?;
+            let mut keystore = Keystore::load(&wallet_path)?;
+            let name = rest.first().map(String::as_str).unwrap_or(MINER);
+            let miner = keystore.get_or_create(name)?.address();
+
+            match blockchain.flush_pending(miner)? {
+                Some(id) => println!("{}", blockchain.get_block_by_id(id).unwrap()),
+                None => println!("No pending transactions to mine"),
+            }
+            blockchain.save_to_file(&chain_path)?;
+            keystore.save(&wallet_path)?;
+        }
+        [command] if command == "validate" => {
+            // Loading normally would refuse an invalid chain before it could
+            // be reported on
+            let blockchain = Blockchain::load_from_file_unchecked(&chain_path)?;
+            match blockchain.validate_chain_detailed() {
+                Ok(()) => println!("The blockchain is valid."),
+                Err(reason) => println!("The blockchain is not valid: {}", reason),
+            }
+        }
+        [command, id] if command == "show" => {
+            let blockchain = Blockchain::load_from_file(&chain_path)?;
+            let block = blockchain
+                .get_block_by_id(id.parse()?)
+                .ok_or_else(|| format!("no block with id {}", id))?;
+            println!("{}", block);
+            for transaction in &block.transactions {
+                println!("    {}", transaction);
+            }
+        }
+        [command, name] if command == "balance" => {
+            let blockchain = Blockchain::load_from_file(&chain_path)?;
+            let keystore = Keystore::load(&wallet_path)?;
+            let address: Address = match keystore.get(name)? {
+                Some(wallet) => wallet.address(),
+                None => name.parse()?,
             };
-            block.add_transaction(transaction);
+            println!("{}", blockchain.balance_of(&address)?);
         }
-        assert!(block.hash.is_some());
-    }
-
-    #[test]
-    fn test_blockchain_creation() {
-        let blockchain = Blockchain::new();
-        assert!(blockchain.get_block_by_id(0).is_some());
-    }
-
-    #[test]
-    fn test_blockchain_addition() {
-        let mut blockchain = Blockchain::new();
-        let transactions: Vec<Transaction> = (1..=5)
-            .map(|i| Transaction {
-                id: i,
-                origin: format!("Sender{}", i),
-                destination: format!("Receiver{}", i),
-                quantity: i * 10,
-            })
-            .collect();
-        blockchain.add_block(transactions);
-        assert!(blockchain.get_block_by_id(1).is_some());
+        [command] if command == "demo" => {
+            for block in &demo()? {
+                println!("{}", block);
+                for transaction in &block.transactions {
+                    println!("    {}", transaction);
+                }
+            }
+        }
+        _ => return Err(USAGE.into()),
     }
 
-    fn test_chain_validation() {
-        let mut blockchain = Blockchain::new();
-
-        let transactions: Vec<Transaction> = (1..=5)
-            .map(|i| Transaction {
-                id: i,
-                origin: format!("Sender{}", i),
-                destination: format!("Receiver{}", i),
-                quantity: i * 10,
-            })
-            .collect();
-        blockchain.add_block(transactions);
-
-        assert!(blockchain.validate_chain());
-
-        // Tamper with the blockchain
-        let tampered_block = blockchain.blocks.get_mut(&1).unwrap();
-        tampered_block.transactions[0].quantity = 100;
-
-        assert!(!blockchain.validate_chain());
-    }
+    Ok(())
 }
-
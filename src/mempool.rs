@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+use crate::transaction::Transaction;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Mempool {
+    transactions: Vec<Transaction>,
+}
+
+impl Mempool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, transaction: Transaction) {
+        self.transactions.push(transaction);
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Removes up to `count` transactions, best paying first. Equal fees go to
+    /// the lower id so assembly is reproducible. A transaction only becomes a
+    /// candidate once the ones its origin sent before it, and the ones queued
+    /// ahead of it that pay its origin, have been taken.
+    pub(crate) fn take(&mut self, count: usize) -> Vec<Transaction> {
+        let mut taken = Vec::new();
+        while taken.len() < count {
+            let pending = &self.transactions;
+            let ready = |index: usize, tx: &Transaction| {
+                let sent_before = pending
+                    .iter()
+                    .any(|other| other.origin == tx.origin && other.nonce < tx.nonce);
+                let funds_it = pending[..index]
+                    .iter()
+                    .any(|other| other.outputs.iter().any(|out| out.recipient == tx.origin));
+                !sent_before && !funds_it
+            };
+            let best = pending
+                .iter()
+                .enumerate()
+                .filter(|(index, tx)| ready(*index, tx))
+                .min_by_key(|(_, tx)| (Reverse(tx.fee), tx.id))
+                .map(|(index, _)| index);
+            match best {
+                Some(index) => taken.push(self.transactions.remove(index)),
+                None => break,
+            }
+        }
+        taken
+    }
+
+    /// Drops the transactions no block at `timestamp` could include.
+    pub(crate) fn purge_expired(&mut self, timestamp: u64) -> usize {
+        let before = self.transactions.len();
+        self.transactions.retain(|tx| !tx.is_expired(timestamp));
+        before - self.transactions.len()
+    }
+
+    pub(crate) fn restore(&mut self, transactions: Vec<Transaction>) {
+        self.transactions.splice(0..0, transactions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::address;
+    use crate::utxo::TxOutput;
+
+    fn pending(id: u64, fee: u64) -> Transaction {
+        Transaction {
+            id,
+            fee,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_take_prefers_higher_fees() {
+        let mut mempool = Mempool::new();
+        for (id, fee) in [(1, 2), (2, 5), (3, 0), (4, 5), (5, 3)] {
+            mempool.push(pending(id, fee));
+        }
+
+        let ids: Vec<u64> = mempool.take(3).iter().map(|tx| tx.id).collect();
+        assert_eq!(ids, vec![2, 4, 5]);
+
+        let ids: Vec<u64> = mempool.take(3).iter().map(|tx| tx.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_take_keeps_each_origin_in_nonce_order() {
+        let mut mempool = Mempool::new();
+        let mut later = pending(1, 9);
+        later.nonce = 1;
+        mempool.push(later);
+        mempool.push(pending(2, 1));
+        let mut other = pending(3, 5);
+        other.origin = address("Other");
+        mempool.push(other);
+
+        let ids: Vec<u64> = mempool.take(3).iter().map(|tx| tx.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_take_waits_for_what_funds_a_transaction() {
+        let mut mempool = Mempool::new();
+        let mut funding = pending(1, 0);
+        funding.outputs = vec![TxOutput::new(address("Alice"), 100)];
+        mempool.push(funding);
+        let mut spend = pending(2, 5);
+        spend.origin = address("Alice");
+        mempool.push(spend);
+        let mut other = pending(3, 1);
+        other.origin = address("Other");
+        mempool.push(other);
+
+        let ids: Vec<u64> = mempool.take(3).iter().map(|tx| tx.id).collect();
+        assert_eq!(ids, vec![3, 1, 2]);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::hash::{sha256, Hash};
+
+pub(crate) fn merkle_root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return sha256("");
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.remove(0)
+}
+
+/// Sibling hashes from leaf to root. The flag is `true` when the sibling sits
+/// to the right of the node being proven.
+pub(crate) fn merkle_proof(leaves: &[Hash], mut index: usize) -> Vec<(Hash, bool)> {
+    let mut proof = Vec::new();
+    let mut level = leaves.to_vec();
+
+    while level.len() > 1 {
+        let sibling_is_right = index.is_multiple_of(2);
+        let sibling = if sibling_is_right {
+            level.get(index + 1).unwrap_or(&level[index])
+        } else {
+            &level[index - 1]
+        };
+        proof.push((*sibling, sibling_is_right));
+
+        level = next_level(&level);
+        index /= 2;
+    }
+
+    proof
+}
+
+pub fn verify_merkle_proof(leaf_hash: &Hash, proof: &[(Hash, bool)], root: &Hash) -> bool {
+    let computed = proof
+        .iter()
+        .fold(*leaf_hash, |node, (sibling, sibling_is_right)| {
+            if *sibling_is_right {
+                hash_pair(&node, sibling)
+            } else {
+                hash_pair(sibling, &node)
+            }
+        });
+    computed == *root
+}
+
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| {
+            let right = pair.get(1).unwrap_or(&pair[0]);
+            hash_pair(&pair[0], right)
+        })
+        .collect()
+}
+
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(left.as_bytes());
+    data[32..].copy_from_slice(right.as_bytes());
+    sha256(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_leaf_is_root() {
+        let leaf = sha256("a");
+        assert_eq!(merkle_root(&[leaf]), leaf);
+    }
+
+    #[test]
+    fn test_odd_leaf_count_duplicates_last() {
+        let leaves: Vec<Hash> = ["a", "b", "c"].iter().map(sha256).collect();
+        let expected = hash_pair(
+            &hash_pair(&leaves[0], &leaves[1]),
+            &hash_pair(&leaves[2], &leaves[2]),
+        );
+        assert_eq!(merkle_root(&leaves), expected);
+    }
+
+    #[test]
+    fn test_proof_for_every_leaf() {
+        let leaves: Vec<Hash> = ["a", "b", "c", "d", "e"].iter().map(sha256).collect();
+        let root = merkle_root(&leaves);
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = merkle_proof(&leaves, index);
+            assert!(verify_merkle_proof(leaf, &proof, &root));
+            assert!(!verify_merkle_proof(&sha256("x"), &proof, &root));
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::address::Address;
+use crate::block::Block;
+use crate::blockchain::{BlockStatus, Blockchain};
+use crate::error::BlockchainError;
+use crate::transaction::Transaction;
+
+/// What nodes send each other. A node that cannot connect a block it was sent
+/// asks the sender for its whole chain.
+#[derive(Debug, Clone)]
+pub enum PeerMessage {
+    Block {
+        block: Box<Block>,
+        reply_to: Sender<PeerMessage>,
+    },
+    ChainRequest {
+        reply_to: Sender<PeerMessage>,
+    },
+    Chain(Vec<Block>),
+}
+
+/// A chain plus the channels to gossip it with other nodes in the same
+/// process. Nothing happens until [`Node::receive_block`] is called.
+pub struct Node {
+    blockchain: Blockchain,
+    inbox: Receiver<PeerMessage>,
+    address: Sender<PeerMessage>,
+    peers: Vec<Sender<PeerMessage>>,
+}
+
+impl Node {
+    pub fn new(blockchain: Blockchain) -> Self {
+        let (address, inbox) = mpsc::channel();
+        Self {
+            blockchain,
+            inbox,
+            address,
+            peers: Vec::new(),
+        }
+    }
+
+    /// Where other nodes send messages for this one.
+    pub fn sender(&self) -> Sender<PeerMessage> {
+        self.address.clone()
+    }
+
+    pub fn connect(&mut self, peer: Sender<PeerMessage>) {
+        self.peers.push(peer);
+    }
+
+    pub fn blockchain(&self) -> &Blockchain {
+        &self.blockchain
+    }
+
+    /// Mines a block locally and broadcasts it.
+    pub fn mine(
+        &mut self,
+        transactions: Vec<Transaction>,
+        miner: Address,
+    ) -> Result<u64, BlockchainError> {
+        let id = self.blockchain.add_block(transactions, miner)?;
+        if let Some(block) = self.blockchain.latest_block() {
+            self.broadcast_block(block);
+        }
+        Ok(id)
+    }
+
+    pub fn broadcast_block(&self, block: &Block) {
+        for peer in &self.peers {
+            // A peer that has gone away just misses out
+            let _ = peer.send(PeerMessage::Block {
+                block: Box::new(block.clone()),
+                reply_to: self.sender(),
+            });
+        }
+    }
+
+    /// Handles every message waiting in the inbox and returns whether the
+    /// chain changed. Blocks that extend the chain, directly or by taking a
+    /// branch past it, are accepted and passed on; a chain with more work
+    /// replaces ours; anything invalid is dropped.
+    pub fn receive_block(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok(message) = self.inbox.try_recv() {
+            match message {
+                PeerMessage::Block { block, reply_to } => {
+                    let height = self.blockchain.height();
+                    let id = block.id;
+                    match self.blockchain.accept_block(*block.clone()) {
+                        Ok(BlockStatus::OnChain) => {
+                            self.broadcast_block(&block);
+                            changed = true;
+                        }
+                        Ok(BlockStatus::OnBranch) => {}
+                        Ok(BlockStatus::Orphan) | Err(_) => {
+                            if id > height {
+                                let _ = reply_to.send(PeerMessage::ChainRequest {
+                                    reply_to: self.sender(),
+                                });
+                            }
+                        }
+                    }
+                }
+                PeerMessage::ChainRequest { reply_to } => {
+                    let blocks = self.blockchain.iter().cloned().collect();
+                    let _ = reply_to.send(PeerMessage::Chain(blocks));
+                }
+                PeerMessage::Chain(blocks) => {
+                    if let Ok(true) = self.blockchain.try_replace_chain(blocks) {
+                        if let Some(block) = self.blockchain.latest_block() {
+                            self.broadcast_block(block);
+                        }
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::Hash;
+    use crate::test_support::{config, issue, miner, wallet};
+
+    fn connected_pair() -> (Node, Node) {
+        // Both start from the very same genesis block
+        let genesis = Block::new(0, Hash::ZERO);
+        let node = || Node::new(Blockchain::with_genesis(config(), genesis.clone()).unwrap());
+        let (mut a, mut b) = (node(), node());
+        a.connect(b.sender());
+        b.connect(a.sender());
+        (a, b)
+    }
+
+    fn tip(node: &Node) -> Option<Hash> {
+        node.blockchain()
+            .latest_block()
+            .and_then(|block| block.hash)
+    }
+
+    #[test]
+    fn test_block_extending_the_tip_is_gossiped() {
+        let (mut a, mut b) = connected_pair();
+        a.mine(issue(1..=5), miner()).unwrap();
+
+        assert!(b.receive_block());
+        assert_eq!(tip(&a), tip(&b));
+        // b passed the block back, and a already has it
+        assert!(!a.receive_block());
+    }
+
+    #[test]
+    fn test_nodes_converge_on_the_longer_chain() {
+        let (mut a, mut b) = connected_pair();
+        a.mine(issue(1..=5), miner()).unwrap();
+        b.mine(issue(1..=5), wallet(1).address()).unwrap();
+        b.mine(issue(6..=10), wallet(1).address()).unwrap();
+
+        // a keeps b's first block on a branch and switches when the second
+        // one arrives
+        assert!(a.receive_block());
+
+        assert_eq!(a.blockchain().height(), 2);
+        assert_eq!(tip(&a), tip(&b));
+        assert!(a.blockchain().validate_chain());
+
+        // Nothing left that would move either node
+        assert!(!b.receive_block());
+        assert!(!a.receive_block());
+        assert_eq!(tip(&a), tip(&b));
+    }
+}
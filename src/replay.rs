@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::clock::{MockClock, SystemClock};
+use crate::config::BlockchainConfig;
+use crate::error::BlockchainError;
+use crate::transaction::Transaction;
+
+/// A confirmed transaction and the timestamp of the block holding it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedTransaction {
+    pub timestamp: u64,
+    pub transaction: Transaction,
+}
+
+impl Blockchain {
+    /// Every transaction after the genesis block, coinbases included, in
+    /// chain order.
+    pub fn transaction_log(&self) -> Vec<LoggedTransaction> {
+        self.iter()
+            .skip(1)
+            .flat_map(|block| {
+                block.transactions.iter().map(|tx| LoggedTransaction {
+                    timestamp: block.timestamp,
+                    transaction: tx.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Rebuilds a chain on top of `genesis` from a transaction log. Each
+    /// coinbase starts a block, mined for its recipient at its timestamp, so
+    /// replaying [`Blockchain::transaction_log`] gives back the same blocks,
+    /// hashes included.
+    pub fn replay(
+        config: BlockchainConfig,
+        genesis: Block,
+        log: impl IntoIterator<Item = LoggedTransaction>,
+    ) -> Result<Blockchain, BlockchainError> {
+        let clock = MockClock::new(genesis.timestamp);
+        let mut blockchain = Blockchain::with_genesis(config, genesis)?;
+        blockchain.set_clock(clock.clone());
+
+        let mut pending: Option<(Transaction, Vec<Transaction>)> = None;
+        for entry in log {
+            let tx = entry.transaction;
+            if !tx.is_coinbase() {
+                match pending.as_mut() {
+                    Some((_, transactions)) => transactions.push(tx),
+                    None => {
+                        return Err(BlockchainError::MissingCoinbase {
+                            block_id: blockchain.height() + 1,
+                        })
+                    }
+                }
+                continue;
+            }
+
+            if let Some((coinbase, transactions)) = pending.take() {
+                replay_block(&mut blockchain, coinbase, transactions)?;
+            }
+            clock.set(entry.timestamp);
+            pending = Some((tx, Vec::new()));
+        }
+        if let Some((coinbase, transactions)) = pending {
+            replay_block(&mut blockchain, coinbase, transactions)?;
+        }
+
+        blockchain.set_clock(SystemClock);
+        Ok(blockchain)
+    }
+}
+
+fn replay_block(
+    blockchain: &mut Blockchain,
+    coinbase: Transaction,
+    transactions: Vec<Transaction>,
+) -> Result<(), BlockchainError> {
+    let miner = coinbase.outputs.first().map(|output| output.recipient);
+    blockchain.add_block(transactions, miner.unwrap_or_default())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{chain, issue, miner, wallet};
+
+    #[test]
+    fn test_replay_reproduces_the_chain() {
+        let mut original = chain();
+        for (first, miner) in [(1, miner()), (6, wallet(1).address()), (11, miner())] {
+            original.add_block(issue(first..first + 5), miner).unwrap();
+        }
+        original.add_block(issue(16..=17), miner()).unwrap();
+
+        let genesis = original.get_block_by_id(0).unwrap().clone();
+        let log = original.transaction_log();
+        assert_eq!(log.len(), 4 + 5 * 3 + 2);
+
+        let replayed = Blockchain::replay(original.config().clone(), genesis, log).unwrap();
+        assert!(replayed
+            .iter()
+            .map(|block| block.hash)
+            .eq(original.iter().map(|block| block.hash)));
+        assert!(replayed.validate_chain());
+    }
+
+    #[test]
+    fn test_replay_needs_a_coinbase_first() {
+        let original = chain();
+        let genesis = original.get_block_by_id(0).unwrap().clone();
+        let timestamp = genesis.timestamp;
+        let log = issue([1]).into_iter().map(|transaction| LoggedTransaction {
+            timestamp,
+            transaction,
+        });
+        assert_eq!(
+            Blockchain::replay(original.config().clone(), genesis, log).err(),
+            Some(BlockchainError::MissingCoinbase { block_id: 1 })
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use crate::shared::SharedBlockchain;
+use crate::transaction::Transaction;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const CHAIN_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default)]
+    pub id: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RpcResponse {
+    pub jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    pub id: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcResponse {
+    fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+
+    fn error(id: Value, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            result: None,
+            error: Some(RpcError {
+                code,
+                message: message.into(),
+            }),
+            id,
+        }
+    }
+}
+
+/// JSON-RPC 2.0 over HTTP POST. Methods:
+///
+/// - `get_block(id)` returns the block or `null`
+/// - `get_height()`
+/// - `submit_transaction(transaction)` takes a signed transaction, since the
+///   server holds no keys to sign one from an origin, destination and quantity
+/// - `would_accept(transaction)` checks a transaction without submitting it
+/// - `validate_chain()`
+pub struct RpcServer {
+    http: tiny_http::Server,
+    blockchain: SharedBlockchain,
+}
+
+impl RpcServer {
+    pub fn bind(addr: impl ToSocketAddrs, blockchain: SharedBlockchain) -> io::Result<Self> {
+        let http = tiny_http::Server::http(addr).map_err(io::Error::other)?;
+        Ok(Self { http, blockchain })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Serves requests until the listener fails.
+    pub fn run(&self) {
+        for mut request in self.http.incoming_requests() {
+            let response = if *request.method() != tiny_http::Method::Post {
+                RpcResponse::error(Value::Null, INVALID_REQUEST, "use POST")
+            } else {
+                let mut body = String::new();
+                match request.as_reader().read_to_string(&mut body) {
+                    Ok(_) => self.handle_body(&body),
+                    Err(err) => RpcResponse::error(Value::Null, PARSE_ERROR, err.to_string()),
+                }
+            };
+
+            let body = serde_json::to_string(&response).unwrap_or_default();
+            let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json")
+                .expect("static header is valid");
+            // A client that went away is not our problem
+            let _ =
+                request.respond(tiny_http::Response::from_string(body).with_header(content_type));
+        }
+    }
+
+    pub fn handle_body(&self, body: &str) -> RpcResponse {
+        match serde_json::from_str::<RpcRequest>(body) {
+            Ok(request) => self.handle(request),
+            Err(err) => RpcResponse::error(Value::Null, PARSE_ERROR, err.to_string()),
+        }
+    }
+
+    pub fn handle(&self, request: RpcRequest) -> RpcResponse {
+        let id = request.id;
+        if request.jsonrpc != "2.0" {
+            return RpcResponse::error(id, INVALID_REQUEST, "jsonrpc must be \"2.0\"");
+        }
+
+        match request.method.as_str() {
+            "get_block" => match param::<u64>(&request.params, 0, "id") {
+                Some(block_id) => {
+                    let block = self
+                        .blockchain
+                        .read(|blockchain| blockchain.get_block_by_id(block_id).cloned());
+                    RpcResponse::result(id, json!(block))
+                }
+                None => RpcResponse::error(id, INVALID_PARAMS, "expected a block id"),
+            },
+            "get_height" => RpcResponse::result(id, json!(self.blockchain.read_height())),
+            "submit_transaction" => match param::<Transaction>(&request.params, 0, "transaction") {
+                Some(transaction) => match self.blockchain.submit_transaction(transaction) {
+                    Ok(block_id) => RpcResponse::result(id, json!({ "block_id": block_id })),
+                    Err(err) => RpcResponse::error(id, CHAIN_ERROR, err.to_string()),
+                },
+                None => RpcResponse::error(id, INVALID_PARAMS, "expected a transaction"),
+            },
+            "would_accept" => match param::<Transaction>(&request.params, 0, "transaction") {
+                Some(transaction) => match self
+                    .blockchain
+                    .read(|blockchain| blockchain.would_accept(&transaction))
+                {
+                    Ok(()) => RpcResponse::result(id, json!(true)),
+                    Err(err) => RpcResponse::error(id, CHAIN_ERROR, err.to_string()),
+                },
+                None => RpcResponse::error(id, INVALID_PARAMS, "expected a transaction"),
+            },
+            "validate_chain" => RpcResponse::result(id, json!(self.blockchain.validate())),
+            method => {
+                RpcResponse::error(id, METHOD_NOT_FOUND, format!("unknown method {}", method))
+            }
+        }
+    }
+}
+
+/// Reads a parameter passed either by position or by name.
+fn param<T: serde::de::DeserializeOwned>(params: &Value, index: usize, name: &str) -> Option<T> {
+    let value = match params {
+        Value::Array(values) => values.get(index)?,
+        Value::Object(values) => values.get(name)?,
+        _ => return None,
+    };
+    serde_json::from_value(value.clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Blockchain;
+    use crate::test_support::{chain, issue};
+
+    fn server() -> RpcServer {
+        RpcServer::bind("127.0.0.1:0", SharedBlockchain::new(chain())).unwrap()
+    }
+
+    fn call(server: &RpcServer, method: &str, params: Value) -> RpcResponse {
+        server.handle(RpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params,
+            id: json!(1),
+        })
+    }
+
+    #[test]
+    fn test_get_block_by_position_or_name() {
+        let server = server();
+        let by_position = call(&server, "get_block", json!([0])).result.unwrap();
+        let by_name = call(&server, "get_block", json!({ "id": 0 }))
+            .result
+            .unwrap();
+
+        assert_eq!(by_position, by_name);
+        assert_eq!(by_position["id"], json!(0));
+        assert_eq!(
+            call(&server, "get_block", json!([5])).result,
+            Some(Value::Null)
+        );
+        assert_eq!(
+            call(&server, "get_block", json!(["zero"]))
+                .error
+                .unwrap()
+                .code,
+            INVALID_PARAMS
+        );
+    }
+
+    #[test]
+    fn test_submit_transaction() {
+        let server = server();
+        let transaction = serde_json::to_value(issue([1]).remove(0)).unwrap();
+        let response = call(&server, "would_accept", json!([transaction]));
+        assert_eq!(response.result, Some(json!(true)));
+        assert_eq!(server.blockchain.read(Blockchain::pending_count), 0);
+
+        let response = call(&server, "submit_transaction", json!([transaction]));
+        assert_eq!(response.result, Some(json!({ "block_id": null })));
+
+        let response = call(&server, "submit_transaction", json!([transaction]));
+        assert_eq!(response.error.unwrap().code, CHAIN_ERROR);
+        assert_eq!(
+            server
+                .blockchain
+                .read(|blockchain| blockchain.pending_count()),
+            1
+        );
+    }
+
+    #[test]
+    fn test_malformed_requests() {
+        let server = server();
+        assert_eq!(server.handle_body("{").error.unwrap().code, PARSE_ERROR);
+        assert_eq!(
+            call(&server, "mine_forever", Value::Null)
+                .error
+                .unwrap()
+                .code,
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            server
+                .handle_body(r#"{"jsonrpc":"1.0","method":"get_height","id":3}"#)
+                .error
+                .unwrap()
+                .code,
+            INVALID_REQUEST
+        );
+    }
+}
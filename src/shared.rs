@@ -0,0 +1,138 @@
+use std::sync::{Arc, RwLock};
+
+use crate::address::Address;
+use crate::blockchain::Blockchain;
+use crate::error::BlockchainError;
+use crate::transaction::Transaction;
+
+/// A chain that can be cloned into several threads. Every method takes the lock
+/// for the duration of the call only.
+#[derive(Clone)]
+pub struct SharedBlockchain {
+    inner: Arc<RwLock<Blockchain>>,
+}
+
+impl SharedBlockchain {
+    pub fn new(blockchain: Blockchain) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(blockchain)),
+        }
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&Blockchain) -> R) -> R {
+        f(&self.inner.read().expect("blockchain lock poisoned"))
+    }
+
+    pub fn write<R>(&self, f: impl FnOnce(&mut Blockchain) -> R) -> R {
+        f(&mut self.inner.write().expect("blockchain lock poisoned"))
+    }
+
+    pub fn read_height(&self) -> u64 {
+        self.read(Blockchain::height)
+    }
+
+    pub fn submit_transaction(
+        &self,
+        transaction: Transaction,
+    ) -> Result<Option<u64>, BlockchainError> {
+        self.write(|blockchain| blockchain.submit_transaction(transaction))
+    }
+
+    pub fn add_block(
+        &self,
+        transactions: Vec<Transaction>,
+        miner: Address,
+    ) -> Result<u64, BlockchainError> {
+        self.write(|blockchain| blockchain.add_block(transactions, miner))
+    }
+
+    pub fn validate(&self) -> bool {
+        self.read(Blockchain::validate_chain)
+    }
+}
+
+impl From<Blockchain> for SharedBlockchain {
+    fn from(blockchain: Blockchain) -> Self {
+        Self::new(blockchain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{address, chain, issue, issuer, miner, transfer_with_nonce};
+    use std::thread;
+
+    #[test]
+    fn test_concurrent_writers_and_readers() {
+        let shared = SharedBlockchain::new(chain());
+
+        let writers: Vec<_> = (0..2)
+            .map(|writer| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for round in 0..10 {
+                        let first = (writer * 10 + round) * 5 + 1;
+                        // The issuer's nonce depends on which writer got in first
+                        shared
+                            .write(|blockchain| {
+                                let nonce = blockchain.next_nonce(&issuer().address());
+                                let transactions = (0..5)
+                                    .map(|offset| {
+                                        let receiver = address(&format!("Receiver{}", first));
+                                        let id = first + offset;
+                                        transfer_with_nonce(
+                                            id,
+                                            nonce + offset,
+                                            &issuer(),
+                                            &receiver,
+                                            10,
+                                        )
+                                    })
+                                    .collect();
+                                blockchain.add_block(transactions, miner())
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        let reader = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let mut last = 0;
+                for _ in 0..100 {
+                    let height = shared.read_height();
+                    assert!(height >= last);
+                    last = height;
+                }
+            })
+        };
+
+        for handle in writers {
+            handle.join().unwrap();
+        }
+        reader.join().unwrap();
+
+        assert_eq!(shared.read_height(), 20);
+        assert!(shared.validate());
+        assert_eq!(
+            shared.read(|blockchain| blockchain.total_supply()),
+            Ok(1_000)
+        );
+    }
+
+    #[test]
+    fn test_submit_transaction_through_the_lock() {
+        let shared = SharedBlockchain::from(chain());
+        for tx in issue(1..=5) {
+            shared.submit_transaction(tx).unwrap();
+        }
+        assert_eq!(shared.read(Blockchain::pending_count), 5);
+
+        shared
+            .write(|blockchain| blockchain.flush_pending(miner()))
+            .unwrap();
+        assert_eq!(shared.read_height(), 1);
+    }
+}
@@ -0,0 +1,124 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use k256::ecdsa;
+use serde::{Deserialize, Serialize};
+
+use crate::address::Address;
+
+/// How transactions are signed and how their origin follows from the key.
+pub trait SignatureScheme: Send + Sync {
+    /// `None` when `secret_key` is not a valid key for the scheme.
+    fn public_key(&self, secret_key: &[u8; 32]) -> Option<Vec<u8>>;
+
+    /// Signs with a key [`SignatureScheme::public_key`] accepts.
+    fn sign(&self, secret_key: &[u8; 32], message: &[u8]) -> Vec<u8>;
+
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool;
+
+    fn derive_address(&self, public_key: &[u8]) -> Address {
+        Address::from_public_key(public_key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ed25519Scheme;
+
+impl SignatureScheme for Ed25519Scheme {
+    fn public_key(&self, secret_key: &[u8; 32]) -> Option<Vec<u8>> {
+        let signing_key = SigningKey::from_bytes(secret_key);
+        Some(signing_key.verifying_key().to_bytes().to_vec())
+    }
+
+    fn sign(&self, secret_key: &[u8; 32], message: &[u8]) -> Vec<u8> {
+        SigningKey::from_bytes(secret_key)
+            .sign(message)
+            .to_bytes()
+            .to_vec()
+    }
+
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        let Ok(public_key) = <[u8; 32]>::try_from(public_key) else {
+            return false;
+        };
+        let Ok(verifying_key) = VerifyingKey::from_bytes(&public_key) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(signature) else {
+            return false;
+        };
+        verifying_key.verify_strict(message, &signature).is_ok()
+    }
+}
+
+/// ECDSA over secp256k1. Public keys are SEC1 compressed, signatures the 64
+/// byte `r || s` form.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Secp256k1Scheme;
+
+impl SignatureScheme for Secp256k1Scheme {
+    fn public_key(&self, secret_key: &[u8; 32]) -> Option<Vec<u8>> {
+        let signing_key = ecdsa::SigningKey::from_slice(secret_key).ok()?;
+        let point = signing_key.verifying_key().to_encoded_point(true);
+        Some(point.as_bytes().to_vec())
+    }
+
+    fn sign(&self, secret_key: &[u8; 32], message: &[u8]) -> Vec<u8> {
+        let signing_key =
+            ecdsa::SigningKey::from_slice(secret_key).expect("not a secp256k1 secret key");
+        let signature: ecdsa::Signature = signing_key.sign(message);
+        signature.to_bytes().to_vec()
+    }
+
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        use k256::ecdsa::signature::Verifier;
+
+        let Ok(verifying_key) = ecdsa::VerifyingKey::from_sec1_bytes(public_key) else {
+            return false;
+        };
+        let Ok(signature) = ecdsa::Signature::from_slice(signature) else {
+            return false;
+        };
+        verifying_key.verify(message, &signature).is_ok()
+    }
+}
+
+/// Picks a [`SignatureScheme`] in a config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureAlgorithm {
+    #[default]
+    Ed25519,
+    Secp256k1,
+}
+
+impl SignatureAlgorithm {
+    pub fn scheme(self) -> &'static dyn SignatureScheme {
+        match self {
+            SignatureAlgorithm::Ed25519 => &Ed25519Scheme,
+            SignatureAlgorithm::Secp256k1 => &Secp256k1Scheme,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_scheme_verifies_its_own_signatures() {
+        for algorithm in [SignatureAlgorithm::Ed25519, SignatureAlgorithm::Secp256k1] {
+            let scheme = algorithm.scheme();
+            let public_key = scheme.public_key(&[7; 32]).unwrap();
+            let signature = scheme.sign(&[7; 32], b"message");
+
+            assert!(scheme.verify(&public_key, b"message", &signature));
+            assert!(!scheme.verify(&public_key, b"massage", &signature));
+        }
+    }
+
+    #[test]
+    fn test_secp256k1_rejects_an_out_of_range_key() {
+        assert!(Secp256k1Scheme.public_key(&[0; 32]).is_none());
+        assert!(Secp256k1Scheme.public_key(&[0xff; 32]).is_none());
+        assert_eq!(Secp256k1Scheme.public_key(&[1; 32]).unwrap().len(), 33);
+    }
+}
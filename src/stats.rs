@@ -0,0 +1,149 @@
+use std::collections::BTreeSet;
+
+use crate::address::Address;
+use crate::blockchain::Blockchain;
+use crate::hash::Hash;
+use crate::transaction::Transaction;
+
+/// Chain-wide figures. Coinbases count towards the accounts they pay but not
+/// towards the transactions or the quantity transferred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainStats {
+    pub block_count: usize,
+    pub transaction_count: usize,
+    pub total_quantity_transferred: u64,
+    pub unique_accounts: usize,
+    pub latest_hash: Option<Hash>,
+}
+
+impl Blockchain {
+    pub fn stats(&self) -> ChainStats {
+        let mut stats = ChainStats {
+            block_count: 0,
+            transaction_count: 0,
+            total_quantity_transferred: 0,
+            unique_accounts: 0,
+            latest_hash: None,
+        };
+        let mut accounts = BTreeSet::new();
+
+        for block in self {
+            stats.block_count += 1;
+            stats.latest_hash = block.hash;
+            for tx in &block.transactions {
+                add_accounts(&mut accounts, tx);
+                if tx.is_coinbase() {
+                    continue;
+                }
+                stats.transaction_count += 1;
+                for output in &tx.outputs {
+                    stats.total_quantity_transferred = stats
+                        .total_quantity_transferred
+                        .saturating_add(output.amount);
+                }
+            }
+        }
+
+        stats.unique_accounts = accounts.len();
+        stats
+    }
+
+    /// Everything the transfers on the chain paid out, coinbases and fees not
+    /// included. Unlike [`ChainStats::total_quantity_transferred`] it cannot
+    /// saturate.
+    pub fn total_transferred(&self) -> u128 {
+        self.iter()
+            .flat_map(|block| &block.transactions)
+            .filter(|tx| !tx.is_coinbase())
+            .flat_map(|tx| &tx.outputs)
+            .map(|output| u128::from(output.amount))
+            .sum()
+    }
+
+    /// Every account that has sent or been paid anything, in order. The
+    /// coinbase's empty origin is not an account.
+    pub fn accounts(&self) -> BTreeSet<Address> {
+        let mut accounts = BTreeSet::new();
+        for tx in self.iter().flat_map(|block| &block.transactions) {
+            add_accounts(&mut accounts, tx);
+        }
+        accounts
+    }
+}
+
+/// Adds whoever `tx` pays, and its origin unless it is a coinbase.
+fn add_accounts(accounts: &mut BTreeSet<Address>, tx: &Transaction) {
+    accounts.extend(tx.outputs.iter().map(|output| output.recipient));
+    if !tx.is_coinbase() {
+        accounts.insert(tx.origin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::demo::{demo, DEMO_BLOCKS};
+    use crate::test_support::{address, chain, issue, issuer, miner};
+
+    #[test]
+    fn test_stats_on_a_twenty_block_chain() {
+        let mut blockchain = chain();
+        for first in (1..=100).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+
+        let stats = blockchain.stats();
+        assert_eq!(stats.block_count, 21);
+        assert_eq!(stats.transaction_count, 100);
+        assert_eq!(
+            stats.total_quantity_transferred,
+            (1..=100).sum::<u64>() * 10
+        );
+        // The issuer, a hundred receivers and the miner
+        assert_eq!(stats.unique_accounts, 102);
+        assert_eq!(stats.latest_hash, blockchain.latest_block().unwrap().hash);
+    }
+
+    #[test]
+    fn test_stats_on_a_fresh_chain() {
+        let blockchain = chain();
+        let stats = blockchain.stats();
+        assert_eq!(stats.block_count, 1);
+        assert_eq!(stats.transaction_count, 0);
+        assert_eq!(stats.unique_accounts, 0);
+        assert!(stats.latest_hash.is_some());
+    }
+
+    #[test]
+    fn test_accounts_lists_senders_and_receivers() {
+        let mut blockchain = chain();
+        assert!(blockchain.accounts().is_empty());
+
+        blockchain.add_block(issue(1..=3), miner()).unwrap();
+        let expected: BTreeSet<_> = [
+            issuer().address(),
+            miner(),
+            address("Receiver1"),
+            address("Receiver2"),
+            address("Receiver3"),
+        ]
+        .into();
+        assert_eq!(blockchain.accounts(), expected);
+    }
+
+    #[test]
+    fn test_total_transferred_on_the_demo_chain() {
+        let blockchain = demo().unwrap();
+        // The issuer sends 100 to each of four accounts, then transfer `id`
+        // moves `id` coins
+        let expected = 4 * 100 + (5..=DEMO_BLOCKS).sum::<u64>();
+        assert_eq!(blockchain.total_transferred(), u128::from(expected));
+        assert_eq!(
+            blockchain.total_transferred(),
+            u128::from(blockchain.stats().total_quantity_transferred)
+        );
+    }
+}
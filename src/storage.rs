@@ -0,0 +1,402 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::error::BlockchainError;
+
+impl Blockchain {
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<Blockchain, BlockchainError> {
+        let blockchain = Self::load_from_file_unchecked(path)?;
+        blockchain
+            .validate_chain_detailed()
+            .map_err(BlockchainError::InvalidChain)?;
+        Ok(blockchain)
+    }
+
+    /// Reads the chain without validating its blocks, for tools that report
+    /// what is wrong with a chain instead of refusing it.
+    pub fn load_from_file_unchecked(path: &Path) -> Result<Blockchain, BlockchainError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| BlockchainError::Io(format!("{}: {}", path.display(), err)))?;
+        Self::parse_json(&contents)
+    }
+
+    /// Same JSON as [`Blockchain::save_to_file`], gzipped.
+    pub fn save_to_file_compressed(&self, path: &Path) -> io::Result<()> {
+        let mut encoder = GzEncoder::new(File::create(path)?, Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()?.sync_all()
+    }
+
+    pub fn load_from_file_compressed(path: &Path) -> Result<Blockchain, BlockchainError> {
+        let file = File::open(path)
+            .map_err(|err| BlockchainError::Io(format!("{}: {}", path.display(), err)))?;
+        let mut contents = String::new();
+        GzDecoder::new(file)
+            .read_to_string(&mut contents)
+            .map_err(|err| BlockchainError::CorruptFile(format!("{}: {}", path.display(), err)))?;
+        Self::from_json(&contents)
+    }
+
+    fn from_json(contents: &str) -> Result<Blockchain, BlockchainError> {
+        let blockchain = Self::parse_json(contents)?;
+        blockchain
+            .validate_chain_detailed()
+            .map_err(BlockchainError::InvalidChain)?;
+        Ok(blockchain)
+    }
+
+    fn parse_json(contents: &str) -> Result<Blockchain, BlockchainError> {
+        if contents.trim().is_empty() {
+            return Err(BlockchainError::EmptyFile);
+        }
+        serde_json::from_str(contents).map_err(|err| BlockchainError::Deserialize(err.to_string()))
+    }
+
+    /// The chain in bincode, which is much smaller than JSON.
+    pub fn to_bincode(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("a chain always serializes")
+    }
+
+    pub fn from_bincode(bytes: &[u8]) -> Result<Blockchain, BlockchainError> {
+        let blockchain: Blockchain = bincode::deserialize(bytes)
+            .map_err(|err| BlockchainError::Deserialize(err.to_string()))?;
+        blockchain
+            .validate_chain_detailed()
+            .map_err(BlockchainError::InvalidChain)?;
+        Ok(blockchain)
+    }
+
+    /// Writes every transaction, coinbases included, as CSV in chain order. A
+    /// transaction paying several accounts gets a line per output.
+    pub fn export_transactions_csv(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "block_id,tx_id,origin,destination,quantity,timestamp")?;
+        for block in self {
+            for tx in &block.transactions {
+                for output in &tx.outputs {
+                    writeln!(
+                        w,
+                        "{},{},{},{},{},{}",
+                        block.id,
+                        tx.id,
+                        csv_field(&tx.origin.to_string()),
+                        csv_field(&output.recipient.to_string()),
+                        output.amount,
+                        block.timestamp
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the chain as a GraphViz digraph. Every block is a node labelled
+    /// with its id and short hash, with an edge to the block it builds on.
+    /// Blocks on competing branches are drawn too, so each tip shows up as a
+    /// node nothing points to.
+    pub fn to_dot(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut blocks: Vec<&Block> = self.iter().collect();
+        let mut drawn = HashSet::new();
+        for tip in self.tips() {
+            // Branches can share blocks below where they split
+            let mut cursor = self.branch_block(&tip);
+            while let Some(block) = cursor {
+                if !drawn.insert(block.hash) {
+                    break;
+                }
+                blocks.push(block);
+                cursor = self.branch_block(&block.previous_hash);
+            }
+        }
+
+        writeln!(w, "digraph chain {{")?;
+        writeln!(w, "    rankdir=RL;")?;
+        for block in &blocks {
+            let hash = block
+                .hash
+                .map_or_else(|| "unsealed".to_string(), |hash| hash.short());
+            writeln!(
+                w,
+                "    \"{}\" [label=\"#{}\\n{}\"];",
+                dot_node(block),
+                block.id,
+                hash
+            )?;
+        }
+        for block in &blocks {
+            if block.id > 0 {
+                writeln!(
+                    w,
+                    "    \"{}\" -> \"{}\";",
+                    dot_node(block),
+                    block.previous_hash
+                )?;
+            }
+        }
+        writeln!(w, "}}")
+    }
+}
+
+fn dot_node(block: &Block) -> String {
+    block
+        .hash
+        .map_or_else(|| format!("unsealed-{}", block.id), |hash| hash.to_string())
+}
+
+/// Quotes a field when it holds a separator, quote or line break, doubling any
+/// quotes inside it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ValidationError;
+    use crate::test_support::{address, chain, issue, miner};
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "simple-blockchain-{}-{}.json",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn sample_chain() -> Blockchain {
+        let mut blockchain = chain();
+        blockchain.add_block(issue(1..=5), miner()).unwrap();
+        blockchain.add_block(issue(6..=10), miner()).unwrap();
+        blockchain
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = temp_path("save-and-load");
+        let blockchain = sample_chain();
+        blockchain.save_to_file(&path).unwrap();
+
+        let loaded = Blockchain::load_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(loaded.validate_chain());
+        assert_eq!(
+            loaded.get_block_by_id(2).unwrap().hash,
+            blockchain.get_block_by_id(2).unwrap().hash
+        );
+    }
+
+    #[test]
+    fn test_load_tampered_chain() {
+        let path = temp_path("tampered");
+        sample_chain().save_to_file(&path).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(&path, contents.replace("\"amount\": 30", "\"amount\": 31")).unwrap();
+
+        let result = Blockchain::load_from_file(&path);
+        let unchecked = Blockchain::load_from_file_unchecked(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            result.err(),
+            Some(BlockchainError::InvalidChain(
+                ValidationError::HashMismatch { .. }
+            ))
+        ));
+        assert!(matches!(
+            unchecked.validate_chain_detailed(),
+            Err(ValidationError::HashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_empty_file() {
+        let path = temp_path("empty");
+        fs::write(&path, "").unwrap();
+
+        let result = Blockchain::load_from_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.err(), Some(BlockchainError::EmptyFile));
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let path = temp_path("missing");
+
+        assert!(matches!(
+            Blockchain::load_from_file(&path),
+            Err(BlockchainError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_transactions_csv() {
+        let blockchain = sample_chain();
+        let mut output = Vec::new();
+        blockchain.export_transactions_csv(&mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "block_id,tx_id,origin,destination,quantity,timestamp"
+        );
+        // Header plus a coinbase and five transfers in each of two blocks
+        assert_eq!(lines.len(), 13);
+
+        let block = blockchain.get_block_by_id(1).unwrap();
+        let coinbase = &block.transactions[0];
+        let first = &block.transactions[1];
+        assert_eq!(
+            lines[1],
+            format!(
+                "1,1,{},{},50,{}",
+                coinbase.origin, coinbase.outputs[0].recipient, block.timestamp
+            )
+        );
+        assert_eq!(
+            lines[2],
+            format!(
+                "1,1,{},{},10,{}",
+                first.origin, first.outputs[0].recipient, block.timestamp
+            )
+        );
+        assert!(lines[12].starts_with("2,10,"));
+    }
+
+    #[test]
+    fn test_dot_export_draws_blocks_and_links() {
+        let mut blockchain = sample_chain();
+        let mut peer: Blockchain =
+            serde_json::from_str(&serde_json::to_string(&blockchain).unwrap()).unwrap();
+        peer.pop_block();
+        peer.add_block(issue(6..=10), address("Rival")).unwrap();
+        let rival = peer.latest_block().unwrap().clone();
+        blockchain.accept_block(rival.clone()).unwrap();
+
+        let mut output = Vec::new();
+        blockchain.to_dot(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[0], "digraph chain {");
+        assert_eq!(lines.last(), Some(&"}"));
+        let genesis = blockchain.get_block_by_id(0).unwrap().hash.unwrap();
+        let tip = blockchain.latest_block().unwrap().hash.unwrap();
+        assert!(lines.contains(
+            &format!("    \"{}\" [label=\"#0\\n{}\"];", genesis, genesis.short()).as_str()
+        ));
+        assert!(
+            lines.contains(&format!("    \"{}\" [label=\"#2\\n{}\"];", tip, tip.short()).as_str())
+        );
+
+        // Both blocks at height 2 build on block 1
+        let parent = blockchain.get_block_by_id(1).unwrap().hash.unwrap();
+        for child in [tip, rival.hash.unwrap()] {
+            assert!(lines.contains(&format!("    \"{}\" -> \"{}\";", child, parent).as_str()));
+        }
+        assert_eq!(output.matches(" -> ").count(), 3);
+        assert!(!output.contains(&format!("-> \"{}\"", crate::hash::Hash::ZERO)));
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let mut blockchain = chain();
+        for first in (1..=100).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+
+        let bytes = blockchain.to_bincode();
+        assert!(bytes.len() < serde_json::to_vec(&blockchain).unwrap().len());
+
+        let decoded = Blockchain::from_bincode(&bytes).unwrap();
+        assert_eq!(decoded.to_bincode(), bytes);
+        assert_eq!(decoded.height(), 20);
+        assert_eq!(decoded.stats(), blockchain.stats());
+    }
+
+    #[test]
+    fn test_bincode_rejects_bad_input() {
+        let blockchain = sample_chain();
+        let mut bytes = blockchain.to_bincode();
+        assert!(matches!(
+            Blockchain::from_bincode(&bytes[..bytes.len() / 2]),
+            Err(BlockchainError::Deserialize(_))
+        ));
+
+        // Flip a byte of the last block's timestamp
+        let timestamp = blockchain.latest_block().unwrap().timestamp;
+        let position = bytes
+            .windows(8)
+            .rposition(|window| window == timestamp.to_le_bytes())
+            .unwrap();
+        bytes[position] ^= 1;
+        assert!(matches!(
+            Blockchain::from_bincode(&bytes),
+            Err(BlockchainError::InvalidChain(_))
+        ));
+    }
+
+    #[test]
+    fn test_save_and_load_compressed() {
+        let path = temp_path("compressed");
+        let blockchain = sample_chain();
+        blockchain.save_to_file_compressed(&path).unwrap();
+        let compressed = fs::metadata(&path).unwrap().len();
+
+        let loaded = Blockchain::load_from_file_compressed(&path);
+        blockchain.save_to_file(&path).unwrap();
+        let plain = fs::metadata(&path).unwrap().len();
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert!(loaded.validate_chain());
+        assert_eq!(loaded.to_bincode(), blockchain.to_bincode());
+        assert!(compressed < plain);
+    }
+
+    #[test]
+    fn test_load_corrupt_compressed_file() {
+        let path = temp_path("corrupt");
+        sample_chain().save_to_file_compressed(&path).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0xff;
+        fs::write(&path, &bytes).unwrap();
+        let corrupt = Blockchain::load_from_file_compressed(&path);
+
+        // Plain JSON is not gzip either
+        sample_chain().save_to_file(&path).unwrap();
+        let plain = Blockchain::load_from_file_compressed(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(corrupt, Err(BlockchainError::CorruptFile(_))));
+        assert!(matches!(plain, Err(BlockchainError::CorruptFile(_))));
+    }
+}
@@ -0,0 +1,84 @@
+use crate::address::Address;
+use crate::blockchain::Blockchain;
+use crate::config::BlockchainConfig;
+use crate::transaction::Transaction;
+use crate::utxo::TxOutput;
+use crate::wallet::Wallet;
+
+pub(crate) fn wallet(seed: u8) -> Wallet {
+    Wallet::from_secret_key(&[seed; 32])
+}
+
+pub(crate) fn issuer() -> Wallet {
+    wallet(0)
+}
+
+pub(crate) fn config() -> BlockchainConfig {
+    BlockchainConfig {
+        issuer: Some(issuer().address()),
+        ..BlockchainConfig::default()
+    }
+}
+
+pub(crate) fn chain() -> Blockchain {
+    Blockchain::new(config())
+}
+
+pub(crate) fn address(name: &str) -> Address {
+    Address::from_public_key(name.as_bytes())
+}
+
+pub(crate) fn miner() -> Address {
+    address("Miner")
+}
+
+pub(crate) fn transfer(
+    id: u64,
+    from: &Wallet,
+    destination: &Address,
+    quantity: u64,
+) -> Transaction {
+    transfer_with_fee(id, from, destination, quantity, 0)
+}
+
+pub(crate) fn transfer_with_nonce(
+    id: u64,
+    nonce: u64,
+    from: &Wallet,
+    destination: &Address,
+    quantity: u64,
+) -> Transaction {
+    let mut tx = transfer(id, from, destination, quantity);
+    tx.nonce = nonce;
+    from.sign(&mut tx);
+    tx
+}
+
+pub(crate) fn transfer_with_fee(
+    id: u64,
+    from: &Wallet,
+    destination: &Address,
+    quantity: u64,
+    fee: u64,
+) -> Transaction {
+    let mut tx = Transaction {
+        id,
+        outputs: vec![TxOutput::new(*destination, quantity)],
+        fee,
+        ..Default::default()
+    };
+    from.sign(&mut tx);
+    tx
+}
+
+/// Issuer transfers to `Receiver{id}`. The issuer's nonce is taken to be
+/// `id - 1`, which holds as long as the issuer has sent nothing else.
+pub(crate) fn issue(ids: impl IntoIterator<Item = u64>) -> Vec<Transaction> {
+    let issuer = issuer();
+    ids.into_iter()
+        .map(|id| {
+            let receiver = address(&format!("Receiver{}", id));
+            transfer_with_nonce(id, id.saturating_sub(1), &issuer, &receiver, id * 10)
+        })
+        .collect()
+}
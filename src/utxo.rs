@@ -0,0 +1,569 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::address::Address;
+use crate::error::BlockchainError;
+use crate::hash::Hash;
+use crate::transaction::Transaction;
+
+/// Points at one output of an earlier transaction. Transactions are referred to
+/// by hash because coinbase ids share their numbers with ordinary transfers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TxInput {
+    pub source_tx: Hash,
+    pub output_index: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TxOutput {
+    pub recipient: Address,
+    pub amount: u64,
+}
+
+impl TxOutput {
+    pub fn new(recipient: Address, amount: u64) -> Self {
+        Self { recipient, amount }
+    }
+}
+
+/// Every output on the chain that has not been spent yet.
+///
+/// A transaction creates one output per entry in its `outputs` and, when its
+/// inputs are worth more than the quantity plus fee, one more after those
+/// returning the change to its origin. Transfers without explicit inputs have theirs picked by
+/// [`UtxoSet::select_inputs`].
+///
+/// Coinbase outputs, other than the genesis allocations, can only be spent
+/// `maturity` blocks after the block that created them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "UtxoData", into = "UtxoData")]
+pub struct UtxoSet {
+    outputs: HashMap<TxInput, TxOutput>,
+    owned: HashMap<Address, BTreeSet<TxInput>>,
+    balances: HashMap<Address, u64>,
+    maturity: u64,
+    coinbase_heights: HashMap<TxInput, u64>,
+    height: u64,
+}
+
+/// Serialized form of a UTXO set: the outputs as a list, since they are keyed
+/// by a struct. Who owns what and the balances follow from them.
+#[derive(Serialize, Deserialize)]
+struct UtxoData {
+    outputs: Vec<(TxInput, TxOutput)>,
+    maturity: u64,
+    coinbase_heights: Vec<(TxInput, u64)>,
+    height: u64,
+}
+
+impl From<UtxoSet> for UtxoData {
+    fn from(utxos: UtxoSet) -> Self {
+        let mut outputs: Vec<_> = utxos.outputs.into_iter().collect();
+        outputs.sort_by_key(|&(input, _)| input);
+        let mut coinbase_heights: Vec<_> = utxos.coinbase_heights.into_iter().collect();
+        coinbase_heights.sort();
+        Self {
+            outputs,
+            maturity: utxos.maturity,
+            coinbase_heights,
+            height: utxos.height,
+        }
+    }
+}
+
+impl TryFrom<UtxoData> for UtxoSet {
+    type Error = BlockchainError;
+
+    fn try_from(data: UtxoData) -> Result<Self, Self::Error> {
+        let mut utxos = UtxoSet::with_maturity(data.maturity);
+        for (input, output) in data.outputs {
+            utxos.insert(input, output)?;
+        }
+        utxos.coinbase_heights = data.coinbase_heights.into_iter().collect();
+        utxos.height = data.height;
+        Ok(utxos)
+    }
+}
+
+impl UtxoSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_maturity(maturity: u64) -> Self {
+        Self {
+            maturity,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the height of the block whose transactions are applied next.
+    pub(crate) fn set_height(&mut self, height: u64) {
+        self.height = height;
+    }
+
+    /// Whether the output can be spent in a block at the current height.
+    pub fn is_mature(&self, input: &TxInput) -> bool {
+        matured(self.coinbase_heights.get(input), self.height, self.maturity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    pub fn get(&self, input: &TxInput) -> Option<&TxOutput> {
+        self.outputs.get(input)
+    }
+
+    pub fn balance(&self, account: &Address) -> u64 {
+        self.balances.get(account).copied().unwrap_or(0)
+    }
+
+    pub fn unspent(&self, account: &Address) -> Vec<TxInput> {
+        self.owned
+            .get(account)
+            .map(|inputs| inputs.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Enough of the account's spendable outputs to cover `amount`, taken in
+    /// a fixed order so every node picks the same ones.
+    pub fn select_inputs(&self, account: &Address, amount: u64) -> Option<Vec<TxInput>> {
+        let owned = self
+            .owned
+            .get(account)?
+            .iter()
+            .filter(|input| self.is_mature(input))
+            .map(|input| (*input, self.outputs[input].amount));
+        pick(owned, amount)
+    }
+
+    /// Spends the transaction's inputs and records its outputs. `mints` lets
+    /// an input-less transaction create its quantity from nothing, which is
+    /// how coinbases and the issuer put coins into circulation.
+    pub(crate) fn apply(&mut self, tx: &Transaction, mints: bool) -> Result<(), BlockchainError> {
+        let changes = plan(self, tx, mints)?;
+        for input in &changes.spent {
+            self.remove(input);
+        }
+        for (input, output) in changes.created {
+            if changes.coinbase && self.height > 0 && self.maturity > 0 {
+                self.coinbase_heights.insert(input, self.height);
+            }
+            self.insert(input, output)?;
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, input: TxInput, output: TxOutput) -> Result<(), BlockchainError> {
+        if output.amount == 0 {
+            return Ok(());
+        }
+        let balance = self.balances.entry(output.recipient).or_insert(0);
+        *balance = balance
+            .checked_add(output.amount)
+            .ok_or(BlockchainError::Overflow)?;
+        self.owned
+            .entry(output.recipient)
+            .or_default()
+            .insert(input);
+        self.outputs.insert(input, output);
+        Ok(())
+    }
+
+    fn remove(&mut self, input: &TxInput) {
+        let Some(output) = self.outputs.remove(input) else {
+            return;
+        };
+        self.coinbase_heights.remove(input);
+        if let Some(balance) = self.balances.get_mut(&output.recipient) {
+            *balance -= output.amount;
+        }
+        if let Some(owned) = self.owned.get_mut(&output.recipient) {
+            owned.remove(input);
+        }
+    }
+}
+
+/// Transactions played on top of a [`UtxoSet`] without changing it, for
+/// checking ones that may never make it into a block. Only what they spend
+/// and create is kept.
+pub(crate) struct UtxoOverlay<'a> {
+    base: &'a UtxoSet,
+    height: u64,
+    spent: HashSet<TxInput>,
+    created: HashMap<TxInput, TxOutput>,
+    coinbase_heights: HashMap<TxInput, u64>,
+    balances: HashMap<Address, u64>,
+}
+
+impl<'a> UtxoOverlay<'a> {
+    /// Plays transactions as if they were in a block at `height`.
+    pub(crate) fn new(base: &'a UtxoSet, height: u64) -> Self {
+        Self {
+            base,
+            height,
+            spent: HashSet::new(),
+            created: HashMap::new(),
+            coinbase_heights: HashMap::new(),
+            balances: HashMap::new(),
+        }
+    }
+
+    /// Same as [`UtxoSet::apply`].
+    pub(crate) fn apply(&mut self, tx: &Transaction, mints: bool) -> Result<(), BlockchainError> {
+        let changes = plan(self, tx, mints)?;
+        for input in changes.spent {
+            if let Some(output) = self.output(&input) {
+                let balance = self.balance(&output.recipient) - output.amount;
+                self.balances.insert(output.recipient, balance);
+            }
+            if self.created.remove(&input).is_none() {
+                self.spent.insert(input);
+            }
+            self.coinbase_heights.remove(&input);
+        }
+        for (input, output) in changes.created {
+            if output.amount == 0 {
+                continue;
+            }
+            let balance = self
+                .balance(&output.recipient)
+                .checked_add(output.amount)
+                .ok_or(BlockchainError::Overflow)?;
+            self.balances.insert(output.recipient, balance);
+            if changes.coinbase && self.height > 0 && self.base.maturity > 0 {
+                self.coinbase_heights.insert(input, self.height);
+            }
+            self.created.insert(input, output);
+        }
+        Ok(())
+    }
+}
+
+/// What applying a transaction needs to know about the unspent outputs,
+/// whether they are all in a [`UtxoSet`] or partly in a [`UtxoOverlay`].
+trait Unspent {
+    fn output(&self, input: &TxInput) -> Option<TxOutput>;
+    fn is_mature(&self, input: &TxInput) -> bool;
+    fn balance(&self, account: &Address) -> u64;
+    fn select_inputs(&self, account: &Address, amount: u64) -> Option<Vec<TxInput>>;
+}
+
+impl Unspent for UtxoSet {
+    fn output(&self, input: &TxInput) -> Option<TxOutput> {
+        self.get(input).copied()
+    }
+
+    fn is_mature(&self, input: &TxInput) -> bool {
+        UtxoSet::is_mature(self, input)
+    }
+
+    fn balance(&self, account: &Address) -> u64 {
+        UtxoSet::balance(self, account)
+    }
+
+    fn select_inputs(&self, account: &Address, amount: u64) -> Option<Vec<TxInput>> {
+        UtxoSet::select_inputs(self, account, amount)
+    }
+}
+
+impl Unspent for UtxoOverlay<'_> {
+    fn output(&self, input: &TxInput) -> Option<TxOutput> {
+        if self.spent.contains(input) {
+            return None;
+        }
+        self.created
+            .get(input)
+            .or_else(|| self.base.get(input))
+            .copied()
+    }
+
+    fn is_mature(&self, input: &TxInput) -> bool {
+        let created = self
+            .coinbase_heights
+            .get(input)
+            .or_else(|| self.base.coinbase_heights.get(input));
+        matured(created, self.height, self.base.maturity)
+    }
+
+    fn balance(&self, account: &Address) -> u64 {
+        self.balances
+            .get(account)
+            .copied()
+            .unwrap_or_else(|| self.base.balance(account))
+    }
+
+    fn select_inputs(&self, account: &Address, amount: u64) -> Option<Vec<TxInput>> {
+        // The same order the set would keep them in once these were applied
+        let mut owned: BTreeSet<TxInput> = self
+            .base
+            .owned
+            .get(account)
+            .into_iter()
+            .flatten()
+            .filter(|input| !self.spent.contains(input))
+            .copied()
+            .collect();
+        owned.extend(
+            self.created
+                .iter()
+                .filter(|(_, output)| output.recipient == *account)
+                .map(|(input, _)| *input),
+        );
+        let owned = owned
+            .into_iter()
+            .filter(|input| self.is_mature(input))
+            .filter_map(|input| Some((input, self.output(&input)?.amount)));
+        pick(owned, amount)
+    }
+}
+
+/// The outputs a transaction spends and the ones it creates, change
+/// included.
+struct Changes {
+    spent: Vec<TxInput>,
+    created: Vec<(TxInput, TxOutput)>,
+    coinbase: bool,
+}
+
+/// Works out what the transaction would do to the unspent outputs, checking
+/// every input before anything changes.
+fn plan(unspent: &impl Unspent, tx: &Transaction, mints: bool) -> Result<Changes, BlockchainError> {
+    let source_tx = tx.hash();
+    let input = |output_index| TxInput {
+        source_tx,
+        output_index,
+    };
+    let mut created: Vec<(TxInput, TxOutput)> = tx
+        .outputs
+        .iter()
+        .enumerate()
+        .map(|(index, output)| (input(index), *output))
+        .collect();
+
+    if tx.is_coinbase() || (mints && tx.inputs.is_empty()) {
+        return Ok(Changes {
+            spent: Vec::new(),
+            created,
+            coinbase: tx.is_coinbase(),
+        });
+    }
+
+    let required = tx.total_cost()?;
+    let inputs = if tx.inputs.is_empty() {
+        match unspent.select_inputs(&tx.origin, required) {
+            Some(inputs) => inputs,
+            None if unspent.balance(&tx.origin) >= required => {
+                return Err(BlockchainError::ImmatureCoinbase { id: tx.id })
+            }
+            None => {
+                return Err(BlockchainError::InsufficientFunds {
+                    account: tx.origin,
+                    available: unspent.balance(&tx.origin),
+                    required,
+                })
+            }
+        }
+    } else {
+        tx.inputs.clone()
+    };
+
+    let mut seen = HashSet::new();
+    let mut available: u64 = 0;
+    for input in &inputs {
+        let spent = unspent
+            .output(input)
+            .filter(|_| seen.insert(*input))
+            .ok_or(BlockchainError::MissingOutput { id: tx.id })?;
+        if spent.recipient != tx.origin {
+            return Err(BlockchainError::InvalidTransaction {
+                id: tx.id,
+                reason: "an input belongs to another account".to_string(),
+            });
+        }
+        if !unspent.is_mature(input) {
+            return Err(BlockchainError::ImmatureCoinbase { id: tx.id });
+        }
+        available = available
+            .checked_add(spent.amount)
+            .ok_or(BlockchainError::Overflow)?;
+    }
+    if available < required {
+        return Err(BlockchainError::InsufficientFunds {
+            account: tx.origin,
+            available,
+            required,
+        });
+    }
+
+    let change = TxOutput::new(tx.origin, available - required);
+    created.push((input(tx.outputs.len()), change));
+    Ok(Changes {
+        spent: inputs,
+        created,
+        coinbase: false,
+    })
+}
+
+/// Takes outputs in the order given until they cover `amount`.
+fn pick(outputs: impl Iterator<Item = (TxInput, u64)>, amount: u64) -> Option<Vec<TxInput>> {
+    let mut selected = Vec::new();
+    let mut total: u64 = 0;
+    for (input, value) in outputs {
+        if total >= amount {
+            break;
+        }
+        total = total.saturating_add(value);
+        selected.push(input);
+    }
+    (total >= amount).then_some(selected)
+}
+
+/// Whether an output created at `created`, if it came from a coinbase, can
+/// be spent at `height`.
+fn matured(created: Option<&u64>, height: u64, maturity: u64) -> bool {
+    created.is_none_or(|&created| height >= created.saturating_add(maturity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{address, issuer, transfer, wallet};
+
+    fn minted(id: u64, recipient: &Address, amount: u64) -> (UtxoSet, Transaction) {
+        let mut utxos = UtxoSet::new();
+        let tx = transfer(id, &issuer(), recipient, amount);
+        utxos.apply(&tx, true).unwrap();
+        (utxos, tx)
+    }
+
+    #[test]
+    fn test_transfer_consumes_inputs_and_returns_change() {
+        let alice = wallet(1);
+        let (mut utxos, funding) = minted(1, &alice.address(), 100);
+        let source = TxInput {
+            source_tx: funding.hash(),
+            output_index: 0,
+        };
+        assert_eq!(utxos.unspent(&alice.address()), vec![source]);
+
+        let mut spend = transfer(2, &alice, &address("Bob"), 30);
+        spend.inputs = vec![source];
+        alice.sign(&mut spend);
+        utxos.apply(&spend, false).unwrap();
+
+        assert!(utxos.get(&source).is_none());
+        assert_eq!(utxos.balance(&alice.address()), 70);
+        assert_eq!(utxos.balance(&address("Bob")), 30);
+        assert_eq!(utxos.len(), 2);
+    }
+
+    #[test]
+    fn test_spent_output_cannot_be_reused() {
+        let alice = wallet(1);
+        let (mut utxos, funding) = minted(1, &alice.address(), 100);
+        let source = TxInput {
+            source_tx: funding.hash(),
+            output_index: 0,
+        };
+
+        let mut first = transfer(2, &alice, &address("Bob"), 30);
+        first.inputs = vec![source];
+        utxos.apply(&first, false).unwrap();
+
+        let mut second = transfer(3, &alice, &address("Bob"), 30);
+        second.inputs = vec![source];
+        assert_eq!(
+            utxos.apply(&second, false),
+            Err(BlockchainError::MissingOutput { id: 3 })
+        );
+
+        let mut twice = transfer(4, &alice, &address("Bob"), 30);
+        let change = TxInput {
+            source_tx: first.hash(),
+            output_index: 1,
+        };
+        twice.inputs = vec![change, change];
+        assert_eq!(
+            utxos.apply(&twice, false),
+            Err(BlockchainError::MissingOutput { id: 4 })
+        );
+        assert_eq!(utxos.balance(&alice.address()), 70);
+    }
+
+    #[test]
+    fn test_input_must_belong_to_origin() {
+        let (alice, mallory) = (wallet(1), wallet(2));
+        let (mut utxos, funding) = minted(1, &alice.address(), 100);
+
+        let mut theft = transfer(2, &mallory, &mallory.address(), 100);
+        theft.outputs[0].recipient = address("Fence");
+        theft.inputs = vec![TxInput {
+            source_tx: funding.hash(),
+            output_index: 0,
+        }];
+        assert!(matches!(
+            utxos.apply(&theft, false),
+            Err(BlockchainError::InvalidTransaction { id: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_overlay_plays_like_the_set_without_changing_it() {
+        let alice = wallet(1);
+        let (utxos, _) = minted(1, &alice.address(), 100);
+        let mut overlay = UtxoOverlay::new(&utxos, 1);
+        let mut played = utxos.clone();
+
+        let first = transfer(2, &alice, &address("Bob"), 30);
+        // Spends the change the first one leaves
+        let second = transfer(3, &alice, &address("Carol"), 50);
+        for tx in [&first, &second] {
+            overlay.apply(tx, false).unwrap();
+            played.apply(tx, false).unwrap();
+        }
+        let third = transfer(4, &alice, &address("Dave"), 30);
+        assert_eq!(
+            overlay.apply(&third, false),
+            Err(BlockchainError::InsufficientFunds {
+                account: alice.address(),
+                available: 20,
+                required: 30,
+            })
+        );
+
+        for account in [alice.address(), address("Bob"), address("Carol")] {
+            assert_eq!(
+                Unspent::balance(&overlay, &account),
+                played.balance(&account)
+            );
+        }
+        assert_eq!(
+            Unspent::select_inputs(&overlay, &alice.address(), 20),
+            played.select_inputs(&alice.address(), 20)
+        );
+        assert_eq!(utxos.balance(&alice.address()), 100);
+        assert_eq!(utxos.len(), 1);
+    }
+
+    #[test]
+    fn test_select_inputs_takes_the_fewest_needed() {
+        let alice = address("Alice");
+        let mut utxos = UtxoSet::new();
+        for id in 1..=3 {
+            utxos
+                .apply(&transfer(id, &issuer(), &alice, 10), true)
+                .unwrap();
+        }
+
+        assert_eq!(utxos.select_inputs(&alice, 15).unwrap().len(), 2);
+        assert_eq!(utxos.select_inputs(&alice, 30).unwrap().len(), 3);
+        assert!(utxos.select_inputs(&alice, 31).is_none());
+        assert!(utxos.select_inputs(&address("Nobody"), 1).is_none());
+    }
+}
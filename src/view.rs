@@ -0,0 +1,79 @@
+use crate::address::Address;
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::error::BlockchainError;
+
+/// A read-only borrow of a chain, for code that only asks questions of it.
+#[derive(Clone, Copy)]
+pub struct ChainView<'a> {
+    blockchain: &'a Blockchain,
+}
+
+impl<'a> ChainView<'a> {
+    pub fn new(blockchain: &'a Blockchain) -> Self {
+        Self { blockchain }
+    }
+
+    pub fn get_block_by_id(&self, id: u64) -> Option<&'a Block> {
+        self.blockchain.get_block_by_id(id)
+    }
+
+    pub fn height(&self) -> u64 {
+        self.blockchain.height()
+    }
+
+    pub fn balance_of(&self, account: &Address) -> Result<u64, BlockchainError> {
+        self.blockchain.balance_of(account)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'a, Block> {
+        self.blockchain.iter()
+    }
+}
+
+impl Blockchain {
+    pub fn view(&self) -> ChainView<'_> {
+        ChainView::new(self)
+    }
+}
+
+impl<'a> IntoIterator for ChainView<'a> {
+    type Item = &'a Block;
+    type IntoIter = std::slice::Iter<'a, Block>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{address, chain, issue, issuer, miner};
+
+    #[test]
+    fn test_view_answers_like_the_chain() {
+        let mut blockchain = chain();
+        for first in (1..=15).step_by(5) {
+            blockchain
+                .add_block(issue(first..first + 5), miner())
+                .unwrap();
+        }
+
+        let view = blockchain.view();
+        let copy = view;
+        assert_eq!(view.height(), blockchain.height());
+        assert_eq!(
+            copy.get_block_by_id(2).unwrap().hash,
+            blockchain.get_block_by_id(2).unwrap().hash
+        );
+        assert!(view.get_block_by_id(9).is_none());
+        for account in [miner(), issuer().address(), address("Receiver3")] {
+            assert_eq!(view.balance_of(&account), blockchain.balance_of(&account));
+        }
+        assert!(view
+            .iter()
+            .map(|block| block.hash)
+            .eq(blockchain.iter().map(|block| block.hash)));
+        assert_eq!(copy.into_iter().count(), blockchain.len());
+    }
+}
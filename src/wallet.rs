@@ -0,0 +1,137 @@
+use ed25519_dalek::SigningKey;
+use rand::rngs::OsRng;
+
+use crate::address::Address;
+use crate::signature::SignatureAlgorithm;
+use crate::transaction::Transaction;
+
+pub struct Wallet {
+    secret_key: [u8; 32],
+    public_key: Vec<u8>,
+    algorithm: SignatureAlgorithm,
+}
+
+impl Wallet {
+    pub fn generate() -> Self {
+        Self::generate_with(SignatureAlgorithm::Ed25519)
+    }
+
+    pub fn generate_with(algorithm: SignatureAlgorithm) -> Self {
+        loop {
+            let secret_key = SigningKey::generate(&mut OsRng).to_bytes();
+            if let Some(wallet) = Self::from_secret_key_with(&secret_key, algorithm) {
+                return wallet;
+            }
+        }
+    }
+
+    pub fn from_secret_key(secret_key: &[u8; 32]) -> Self {
+        Self::from_secret_key_with(secret_key, SignatureAlgorithm::Ed25519)
+            .expect("every 32 bytes make an ed25519 key")
+    }
+
+    /// `None` when the bytes are not a valid key for `algorithm`.
+    pub fn from_secret_key_with(
+        secret_key: &[u8; 32],
+        algorithm: SignatureAlgorithm,
+    ) -> Option<Self> {
+        let public_key = algorithm.scheme().public_key(secret_key)?;
+        Some(Self {
+            secret_key: *secret_key,
+            public_key,
+            algorithm,
+        })
+    }
+
+    pub fn secret_key(&self) -> [u8; 32] {
+        self.secret_key
+    }
+
+    pub fn public_key(&self) -> Vec<u8> {
+        self.public_key.clone()
+    }
+
+    pub fn algorithm(&self) -> SignatureAlgorithm {
+        self.algorithm
+    }
+
+    pub fn address(&self) -> Address {
+        self.algorithm.scheme().derive_address(&self.public_key)
+    }
+
+    pub fn sign_message(&self, message: &[u8]) -> Vec<u8> {
+        self.algorithm.scheme().sign(&self.secret_key, message)
+    }
+
+    pub fn sign(&self, tx: &mut Transaction) {
+        tx.origin = self.address();
+        tx.public_key = self.public_key();
+        tx.signature = self.sign_message(&tx.signing_payload());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::address;
+    use crate::utxo::TxOutput;
+
+    fn unsigned(destination: &str, quantity: u64) -> Transaction {
+        Transaction {
+            id: 1,
+            outputs: vec![TxOutput::new(address(destination), quantity)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_signed_transaction_verifies() {
+        let wallet = Wallet::generate();
+        let mut tx = unsigned("Bob", 10);
+        wallet.sign(&mut tx);
+
+        assert_eq!(tx.origin, wallet.address());
+        assert!(tx.verify_signature());
+    }
+
+    #[test]
+    fn test_tampered_transaction_fails_verification() {
+        let wallet = Wallet::generate();
+        let mut tx = unsigned("Bob", 10);
+        wallet.sign(&mut tx);
+        tx.outputs[0].amount = 1_000;
+
+        assert!(!tx.verify_signature());
+    }
+
+    #[test]
+    fn test_forged_origin_fails_verification() {
+        let victim = Wallet::generate();
+        let forger = Wallet::generate();
+        let mut tx = unsigned("Mallory", 10);
+        forger.sign(&mut tx);
+        tx.origin = victim.address();
+
+        assert!(!tx.verify_signature());
+    }
+
+    #[test]
+    fn test_signatures_do_not_verify_under_another_scheme() {
+        for (signer, other) in [
+            (SignatureAlgorithm::Ed25519, SignatureAlgorithm::Secp256k1),
+            (SignatureAlgorithm::Secp256k1, SignatureAlgorithm::Ed25519),
+        ] {
+            let wallet = Wallet::generate_with(signer);
+            let mut tx = unsigned("Bob", 10);
+            wallet.sign(&mut tx);
+
+            assert!(tx.verify_signature_with(signer.scheme()));
+            assert!(!tx.verify_signature_with(other.scheme()));
+        }
+    }
+
+    #[test]
+    fn test_unsigned_transaction_fails_verification() {
+        assert!(!unsigned("Bob", 10).verify_signature());
+    }
+}
@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn run(dir: &PathBuf, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_simple-blockchain"))
+        .arg("--dir")
+        .arg(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    let text = String::from_utf8_lossy(&output.stdout).into_owned()
+        + &String::from_utf8_lossy(&output.stderr);
+    (output.status.success(), text)
+}
+
+#[test]
+fn test_cli_round_trip() {
+    let dir = std::env::temp_dir().join(format!("simple_blockchain_cli_{}", std::process::id()));
+
+    assert!(run(&dir, &["new"]).0);
+    assert!(run(&dir, &["add-tx", "issuer", "alice", "100"]).0);
+    assert!(run(&dir, &["add-tx", "alice", "bob", "30"]).0);
+
+    let (ok, output) = run(&dir, &["add-tx", "bob", "alice", "500"]);
+    assert!(!ok);
+    assert!(output.contains("needs 500"));
+
+    let (ok, output) = run(&dir, &["mine"]);
+    assert!(ok);
+    assert!(output.starts_with("Block #1 |"));
+
+    assert_eq!(run(&dir, &["balance", "alice"]).1.trim(), "70");
+    assert_eq!(run(&dir, &["balance", "miner"]).1.trim(), "50");
+    assert!(run(&dir, &["validate"]).1.contains("is valid"));
+
+    let (ok, output) = run(&dir, &["show", "1"]);
+    assert!(ok);
+    assert_eq!(output.lines().count(), 4);
+
+    assert!(!run(&dir, &["show", "9"]).0);
+    assert!(!run(&dir, &["frobnicate"]).0);
+
+    let chain_path = dir.join("chain.json");
+    let contents = fs::read_to_string(&chain_path).unwrap();
+    fs::write(
+        &chain_path,
+        contents.replace("\"amount\": 30", "\"amount\": 31"),
+    )
+    .unwrap();
+    let (ok, output) = run(&dir, &["validate"]);
+    assert!(ok);
+    assert!(output.contains("is not valid: block 1"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,69 @@
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+use simple_blockchain::{
+    BlockFeed, Blockchain, BlockchainConfig, SharedBlockchain, Transaction, Wallet,
+};
+use tungstenite::Message;
+
+fn next_block(client: &mut tungstenite::WebSocket<impl std::io::Read + std::io::Write>) -> Value {
+    loop {
+        match client.read().unwrap() {
+            Message::Text(json) => return serde_json::from_str(&json).unwrap(),
+            _ => continue,
+        }
+    }
+}
+
+#[test]
+fn test_subscribers_receive_new_blocks() {
+    let issuer = Wallet::generate();
+    let miner = Wallet::generate().address();
+    let blockchain = SharedBlockchain::new(Blockchain::new(BlockchainConfig {
+        issuer: Some(issuer.address()),
+        ..BlockchainConfig::default()
+    }));
+
+    let feed = BlockFeed::bind("127.0.0.1:0", &blockchain).unwrap();
+    let url = format!("ws://{}", feed.local_addr().unwrap());
+    let feed = Arc::new(feed);
+    let server = Arc::clone(&feed);
+    thread::spawn(move || server.run());
+
+    // A client that connects and never upgrades does not hold up the rest
+    let _silent = TcpStream::connect(feed.local_addr().unwrap()).unwrap();
+    let (mut first, _) = tungstenite::connect(&url).unwrap();
+    let (mut second, _) = tungstenite::connect(&url).unwrap();
+    while feed.subscriber_count() < 2 {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut tx = Transaction::simple(issuer.address(), Wallet::generate().address(), 10);
+    tx.id = 1;
+    issuer.sign(&mut tx);
+    blockchain.add_block(vec![tx], miner).unwrap();
+
+    let hash = blockchain.read(|chain| chain.latest_block().unwrap().hash);
+    for client in [&mut first, &mut second] {
+        let block = next_block(client);
+        assert_eq!(block["id"], 1);
+        assert_eq!(block["hash"], serde_json::json!(hash));
+    }
+
+    // A client that leaves stops being a subscriber
+    second.close(None).unwrap();
+    while second.read().is_ok() {}
+    while feed.subscriber_count() > 1 {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut tx = Transaction::simple(issuer.address(), Wallet::generate().address(), 10);
+    tx.id = 2;
+    tx.nonce = 1;
+    issuer.sign(&mut tx);
+    blockchain.add_block(vec![tx], miner).unwrap();
+    assert_eq!(next_block(&mut first)["id"], 2);
+}
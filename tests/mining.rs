@@ -0,0 +1,68 @@
+use simple_blockchain::{Blockchain, BlockchainConfig, Transaction, Wallet};
+
+fn config(issuer: &Wallet) -> BlockchainConfig {
+    BlockchainConfig {
+        issuer: Some(issuer.address()),
+        difficulty: 2,
+        ..BlockchainConfig::default()
+    }
+}
+
+fn transfer(issuer: &Wallet, nonce: u64, quantity: u64) -> Transaction {
+    let receiver = Wallet::generate().address();
+    let mut tx = Transaction::simple(issuer.address(), receiver, quantity);
+    tx.nonce = nonce;
+    issuer.sign(&mut tx);
+    tx
+}
+
+#[tokio::test]
+async fn test_mine_block_async() {
+    let issuer = Wallet::generate();
+    let miner = Wallet::generate().address();
+    let mut blockchain = Blockchain::new(config(&issuer));
+    assert!(blockchain.mine_block_async(miner).await.unwrap().is_none());
+
+    blockchain
+        .submit_transaction(transfer(&issuer, 0, 10))
+        .unwrap();
+    let block = blockchain.mine_block_async(miner).await.unwrap().unwrap();
+
+    assert_eq!(block.id, 1);
+    assert!(block.hash.unwrap().to_string().starts_with("00"));
+    assert_eq!(blockchain.height(), 1);
+    assert_eq!(blockchain.pending_count(), 0);
+    assert!(blockchain.validate_chain());
+}
+
+#[tokio::test]
+async fn test_mine_from_channel() {
+    let issuer = Wallet::generate();
+    let miner = Wallet::generate().address();
+    let mut blockchain = Blockchain::new(config(&issuer));
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(8);
+    tokio::spawn(async move {
+        for nonce in 0..3 {
+            sender.send(transfer(&issuer, nonce, 10)).await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+
+    let block = blockchain
+        .mine_from_channel(&mut receiver, miner)
+        .await
+        .unwrap()
+        .unwrap();
+    // The coinbase and everything that was waiting in the channel
+    assert_eq!(block.transactions.len(), 4);
+    assert!(blockchain.validate_chain());
+
+    // The sender is gone, so nothing more will arrive
+    assert!(blockchain
+        .mine_from_channel(&mut receiver, miner)
+        .await
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,55 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+
+use serde_json::{json, Value};
+use simple_blockchain::{Blockchain, RpcServer, SharedBlockchain};
+
+fn post(addr: SocketAddr, body: &Value) -> Value {
+    let body = body.to_string();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        addr,
+        body.len(),
+        body
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let (_, payload) = response.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str(payload).unwrap()
+}
+
+#[test]
+fn test_fetch_genesis_block_over_http() {
+    let blockchain = SharedBlockchain::new(Blockchain::default());
+    let genesis_hash = blockchain.read(|chain| chain.get_block_by_id(0).unwrap().hash);
+
+    let server = RpcServer::bind("127.0.0.1:0", blockchain).unwrap();
+    let addr = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    let response = post(
+        addr,
+        &json!({ "jsonrpc": "2.0", "method": "get_block", "params": [0], "id": 7 }),
+    );
+    assert_eq!(response["id"], json!(7));
+    assert_eq!(response["result"]["id"], json!(0));
+    assert_eq!(response["result"]["hash"], json!(genesis_hash));
+
+    let response = post(
+        addr,
+        &json!({ "jsonrpc": "2.0", "method": "get_height", "id": 8 }),
+    );
+    assert_eq!(response["result"], json!(0));
+
+    let response = post(
+        addr,
+        &json!({ "jsonrpc": "2.0", "method": "validate_chain", "id": 9 }),
+    );
+    assert_eq!(response["result"], json!(true));
+}